@@ -0,0 +1,15 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS `subscriber_filters` (
+  `chat_id` INTEGER NOT NULL,
+  -- All limits are inclusive, NULL means "don't care".
+  `min_price` INTEGER,
+  `max_price` INTEGER,
+  `min_area` INTEGER,
+  `min_rooms` INTEGER,
+  PRIMARY KEY (`chat_id`),
+  FOREIGN KEY (`chat_id`) REFERENCES `subscribers` (`chat_id`) ON DELETE CASCADE
+);
+
+-- Existing subscribers keep the limits that used to be hardcoded in the bot.
+INSERT INTO `subscriber_filters` (`chat_id`, `max_price`, `min_area`)
+SELECT `chat_id`, 1799, 55 FROM `subscribers`;
@@ -0,0 +1,16 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS `regions` (
+  `id` INTEGER PRIMARY KEY AUTOINCREMENT,
+  `chat_id` INTEGER NOT NULL,
+  `name` TEXT NOT NULL,
+  -- A GeoJSON (Multi)Polygon geometry
+  `geometry` TEXT NOT NULL,
+  UNIQUE (`chat_id`, `name`),
+  FOREIGN KEY (`chat_id`) REFERENCES `subscribers` (`chat_id`) ON DELETE CASCADE
+);
+
+-- Existing subscribers keep the area that used to be hardcoded in the bot
+-- (Rotterdam-Oost/Kralingen).
+INSERT INTO `regions` (`chat_id`, `name`, `geometry`)
+SELECT `chat_id`, 'kralingen', '{"type":"MultiPolygon","coordinates":[[[[4.4496346,51.9359046],[4.4674873,51.9398733],[4.484911,51.9416723],[4.4922066,51.9420956],[4.5062828,51.9333644],[4.520359,51.9271722],[4.5143509,51.9156323],[4.4977856,51.9180146],[4.4874001,51.9115556],[4.4802761,51.9052544],[4.4679165,51.9012827],[4.4629383,51.9093847],[4.4572735,51.9088022],[4.455986,51.9121909],[4.4551277,51.9155793],[4.4544411,51.9225672],[4.4496346,51.9360633],[4.4496346,51.9359046]]]]}'
+FROM `subscribers`;
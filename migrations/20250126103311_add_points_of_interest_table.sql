@@ -0,0 +1,12 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS `points_of_interest` (
+  `id` INTEGER PRIMARY KEY AUTOINCREMENT,
+  `chat_id` INTEGER NOT NULL,
+  `name` TEXT NOT NULL,
+  `latitude` REAL NOT NULL,
+  `longitude` REAL NOT NULL,
+  -- In meters
+  `max_distance` REAL NOT NULL,
+  UNIQUE (`chat_id`, `name`),
+  FOREIGN KEY (`chat_id`) REFERENCES `subscribers` (`chat_id`) ON DELETE CASCADE
+);
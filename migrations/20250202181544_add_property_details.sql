@@ -0,0 +1,12 @@
+-- Add migration script here
+ALTER TABLE `properties` ADD COLUMN `rooms` INTEGER;
+ALTER TABLE `properties` ADD COLUMN `bedrooms` INTEGER;
+-- "furnished", "upholstered" or "shell"
+ALTER TABLE `properties` ADD COLUMN `interior` TEXT;
+ALTER TABLE `properties` ADD COLUMN `energy_label` TEXT;
+-- "immediately" or a YYYY-MM-DD date
+ALTER TABLE `properties` ADD COLUMN `available_from` TEXT;
+ALTER TABLE `properties` ADD COLUMN `deposit` INTEGER;
+ALTER TABLE `properties` ADD COLUMN `postcode` TEXT;
+
+ALTER TABLE `subscriber_filters` ADD COLUMN `interior` TEXT;
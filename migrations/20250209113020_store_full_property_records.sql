@@ -0,0 +1,39 @@
+-- Add migration script here
+-- SQLite can't change the primary key of an existing table, so recreate it.
+CREATE TABLE `properties_new` (
+  `id` INTEGER PRIMARY KEY AUTOINCREMENT,
+  `url` varchar(1024) NOT NULL UNIQUE,
+  -- The website the property was scraped from, NULL for properties saved before this was recorded
+  `site` TEXT,
+  `title` TEXT NOT NULL DEFAULT '',
+  `price` INTEGER,
+  `area` INTEGER,
+  `latitude` REAL,
+  `longitude` REAL,
+  `rooms` INTEGER,
+  `bedrooms` INTEGER,
+  `interior` TEXT,
+  `energy_label` TEXT,
+  `available_from` TEXT,
+  `deposit` INTEGER,
+  `postcode` TEXT,
+  -- Unix timestamps
+  `first_seen_at` INTEGER NOT NULL,
+  `last_seen_at` INTEGER NOT NULL,
+  -- Set when the property is no longer listed on its website
+  `delisted_at` INTEGER
+);
+
+INSERT INTO `properties_new` (
+  `url`, `rooms`, `bedrooms`, `interior`, `energy_label`, `available_from`, `deposit`, `postcode`,
+  `first_seen_at`, `last_seen_at`
+)
+SELECT
+  `url`, `rooms`, `bedrooms`, `interior`, `energy_label`, `available_from`, `deposit`, `postcode`,
+  CAST(strftime('%s', 'now') AS INTEGER), CAST(strftime('%s', 'now') AS INTEGER)
+FROM `properties`;
+
+DROP TABLE `properties`;
+ALTER TABLE `properties_new` RENAME TO `properties`;
+
+CREATE INDEX `properties_site` ON `properties` (`site`, `delisted_at`);
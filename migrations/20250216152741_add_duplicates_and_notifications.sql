@@ -0,0 +1,17 @@
+-- Add migration script here
+-- Set when the property was already listed on another website
+ALTER TABLE `properties` ADD COLUMN `duplicate_of` INTEGER REFERENCES `properties` (`id`);
+
+CREATE TABLE IF NOT EXISTS `notifications` (
+  `id` INTEGER PRIMARY KEY AUTOINCREMENT,
+  `chat_id` INTEGER NOT NULL,
+  `property_id` INTEGER NOT NULL,
+  `message_id` INTEGER NOT NULL,
+  -- Kept so the message can be extended when the property shows up on another website
+  `text` TEXT NOT NULL,
+  `sent_at` INTEGER NOT NULL,
+  FOREIGN KEY (`chat_id`) REFERENCES `subscribers` (`chat_id`) ON DELETE CASCADE,
+  FOREIGN KEY (`property_id`) REFERENCES `properties` (`id`) ON DELETE CASCADE
+);
+
+CREATE INDEX `notifications_property` ON `notifications` (`property_id`, `chat_id`);
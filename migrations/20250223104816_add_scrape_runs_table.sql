@@ -0,0 +1,15 @@
+-- Add migration script here
+-- One row per scrape of a website, to notice when a scraper breaks
+CREATE TABLE IF NOT EXISTS `scrape_runs` (
+  `id` INTEGER PRIMARY KEY AUTOINCREMENT,
+  `site` TEXT NOT NULL,
+  `started_at` INTEGER NOT NULL,
+  `finished_at` INTEGER NOT NULL,
+  -- NULL if the scrape succeeded
+  `error` TEXT,
+  -- These are only set if the scrape succeeded
+  `listings` INTEGER,
+  `new_listings` INTEGER
+);
+
+CREATE INDEX `scrape_runs_site` ON `scrape_runs` (`site`, `started_at`);
@@ -0,0 +1,3 @@
+-- Add migration script here
+-- Listings on the page which couldn't be parsed, only set if the scrape succeeded
+ALTER TABLE `scrape_runs` ADD COLUMN `parse_errors` INTEGER;
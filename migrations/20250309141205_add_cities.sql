@@ -0,0 +1,15 @@
+-- Add migration script here
+-- Only Rotterdam was scraped before cities were configurable
+ALTER TABLE `properties` ADD COLUMN `city` TEXT;
+UPDATE `properties` SET `city` = 'rotterdam';
+
+CREATE TABLE IF NOT EXISTS `subscriber_cities` (
+  `chat_id` INTEGER NOT NULL,
+  `city` TEXT NOT NULL,
+  PRIMARY KEY (`chat_id`, `city`),
+  FOREIGN KEY (`chat_id`) REFERENCES `subscribers` (`chat_id`) ON DELETE CASCADE
+);
+
+-- Existing subscribers keep getting Rotterdam properties only
+INSERT INTO `subscriber_cities` (`chat_id`, `city`)
+SELECT `chat_id`, 'rotterdam' FROM `subscribers`;
@@ -0,0 +1,3 @@
+-- Add migration script here
+-- Subscribers don't get notifications until this time, NULL if they aren't paused
+ALTER TABLE `subscribers` ADD COLUMN `paused_until` INTEGER;
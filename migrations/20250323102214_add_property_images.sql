@@ -0,0 +1,6 @@
+-- Add migration script here
+-- The first photo of the property, shown in notifications
+ALTER TABLE `properties` ADD COLUMN `image_url` TEXT;
+
+-- Photo messages have a caption instead of a text, which is edited differently
+ALTER TABLE `notifications` ADD COLUMN `has_photo` BOOLEAN NOT NULL DEFAULT FALSE;
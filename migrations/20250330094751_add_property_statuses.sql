@@ -0,0 +1,14 @@
+-- Add migration script here
+-- What a subscriber did with a property they were notified about
+CREATE TABLE IF NOT EXISTS `property_statuses` (
+  `chat_id` INTEGER NOT NULL,
+  `property_id` INTEGER NOT NULL,
+  -- "interested", "applied", "rejected" or "hidden"
+  `status` TEXT NOT NULL,
+  `updated_at` INTEGER NOT NULL,
+  PRIMARY KEY (`chat_id`, `property_id`),
+  FOREIGN KEY (`chat_id`) REFERENCES `subscribers` (`chat_id`) ON DELETE CASCADE,
+  FOREIGN KEY (`property_id`) REFERENCES `properties` (`id`) ON DELETE CASCADE
+);
+
+CREATE INDEX `property_statuses_status` ON `property_statuses` (`chat_id`, `status`);
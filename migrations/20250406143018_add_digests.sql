@@ -0,0 +1,17 @@
+-- Add migration script here
+-- "instant", "hourly" or "daily"
+ALTER TABLE `subscribers` ADD COLUMN `delivery` TEXT NOT NULL DEFAULT 'instant';
+-- The time of daily digests, as HH:MM in UTC
+ALTER TABLE `subscribers` ADD COLUMN `digest_time` TEXT;
+-- Unix timestamp of the last digest, to know when the next one is due
+ALTER TABLE `subscribers` ADD COLUMN `last_digest_at` INTEGER;
+
+-- Properties to include in the next digest of a subscriber
+CREATE TABLE IF NOT EXISTS `queued_notifications` (
+  `chat_id` INTEGER NOT NULL,
+  `property_id` INTEGER NOT NULL,
+  `queued_at` INTEGER NOT NULL,
+  PRIMARY KEY (`chat_id`, `property_id`),
+  FOREIGN KEY (`chat_id`) REFERENCES `subscribers` (`chat_id`) ON DELETE CASCADE,
+  FOREIGN KEY (`property_id`) REFERENCES `properties` (`id`) ON DELETE CASCADE
+);
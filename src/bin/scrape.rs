@@ -1,41 +1,44 @@
+use std::path::PathBuf;
+
 use anyhow::Context;
-use clap::{Parser, ValueEnum};
-use nlhousefinder::scraping::{
-    huurwoningen::HuurwoningenScraper, ikwilhuren::IkwilhurenScraper, pararius::ParariusScraper,
-    rotterdamwonen::RotterdamWonenScraper, verra::VerraMakelaarsScraper, vesteda::VestedaScraper,
-    WebsiteScraper,
+use clap::Parser;
+use nlhousefinder::{
+    config::HttpConfig,
+    scraping::{http::HttpClient, selector::SelectorDefinition, Definition, Search, Website},
 };
 
 #[derive(Parser)]
 struct Args {
+    /// A built-in scraper, or the name of the website in the definition file
     website: Website,
-}
-
-#[derive(ValueEnum, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
-enum Website {
-    Pararius,
-    Huurwoningen,
-    Ikwilhuren,
-    RotterdamWonen,
-    Verra,
-    Vesteda,
+    /// A site definition file, to try it out
+    #[arg(long)]
+    definition: Option<PathBuf>,
+    /// The city to search, as used in website URLs
+    #[arg(long, default_value = "rotterdam")]
+    city: String,
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let args = Args::parse();
 
-    let scraper: Box<dyn WebsiteScraper> = match args.website {
-        Website::Pararius => Box::new(ParariusScraper::default()),
-        Website::Huurwoningen => Box::new(HuurwoningenScraper::default()),
-        Website::Ikwilhuren => Box::new(IkwilhurenScraper::default()),
-        Website::RotterdamWonen => Box::new(RotterdamWonenScraper::default()),
-        Website::Verra => Box::new(VerraMakelaarsScraper::default()),
-        Website::Vesteda => Box::new(VestedaScraper::default()),
+    let client = HttpClient::new(&HttpConfig::default())?;
+    let search = Search {
+        city: args.city,
+        ..Default::default()
     };
+    let definitions = args
+        .definition
+        .map(|path| SelectorDefinition::load(&path))
+        .transpose()?
+        .map(|definition| Definition::Selector(Box::new(definition)));
+    let scraper = args
+        .website
+        .scraper(client, &search, definitions.as_slice())?;
 
-    let properties = scraper.list_properties().await?;
-    let first_property = properties.first().context("no properties")?;
+    let listings = scraper.list_properties().await?;
+    let first_property = listings.properties().first().context("no properties")?;
     let full_property = scraper.full(first_property.clone()).await?;
 
     println!("{full_property:#?}");
@@ -0,0 +1,580 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::Context;
+use rand::Rng;
+use serde::Deserialize;
+
+use crate::{
+    filter::Filter,
+    scraping::{
+        realtimelistings::RealtimeListingsDefinition, selector::SelectorDefinition, Definition,
+        Interior, Search, Website,
+    },
+};
+
+/// Bot configuration, loaded from a TOML file.
+/// Every field has a default so an empty file (or no file at all) is a valid configuration.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub database_url: String,
+    /// The Telegram chat to send alerts about broken scrapers to.
+    /// Without it problems are only logged.
+    pub admin_chat_id: Option<i64>,
+    /// How long to wait between scrapes of a website, unless overridden per scraper.
+    pub scrape_interval_secs: u64,
+    /// A random delay of up to this many seconds is added to every scrape interval,
+    /// so websites aren't scraped at predictable times.
+    pub scrape_jitter_secs: u64,
+    /// Scrapes taking longer than this are cancelled.
+    pub scrape_timeout_secs: u64,
+    /// How many websites can be scraped at the same time.
+    pub max_concurrent_scrapes: usize,
+    /// How many pages of listings to scrape at most, unless overridden per scraper.
+    /// Next pages are only scraped until a page has a property we already know.
+    pub max_pages: u32,
+    pub http: HttpConfig,
+    pub search: SearchConfig,
+    pub health: HealthConfig,
+    /// The filter new subscribers start out with.
+    pub default_filter: FilterConfig,
+    pub scrapers: HashMap<Website, ScraperConfig>,
+    /// Definition files of websites without a scraper of their own, relative to the
+    /// configuration file. See `site.example.toml`.
+    pub site_definitions: Vec<PathBuf>,
+    /// Broker websites with a realtime listings feed, like Verra's.
+    pub brokers: Vec<RealtimeListingsDefinition>,
+    /// The loaded `site_definitions`, and the `brokers`.
+    #[serde(skip)]
+    pub(crate) definitions: Vec<Definition>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HttpConfig {
+    pub user_agent: String,
+    /// Timeout of a single request.
+    pub timeout_secs: u64,
+    /// How often to retry requests which failed because of rate limiting or server errors.
+    pub max_retries: u32,
+    /// The delay before the first retry, doubled for every next retry.
+    /// A Retry-After header sent by the website takes precedence.
+    pub retry_backoff_ms: u64,
+    /// The minimum time between two requests to the same host.
+    pub min_request_interval_ms: u64,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            user_agent: concat!(
+                "nlhousefinder/",
+                env!("CARGO_PKG_VERSION"),
+                " (+https://github.com/OmriSteiner/nlhousefinder)"
+            )
+            .to_string(),
+            timeout_secs: 30,
+            max_retries: 3,
+            retry_backoff_ms: 2000,
+            min_request_interval_ms: 2000,
+        }
+    }
+}
+
+/// What to search for on the websites.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SearchConfig {
+    /// The cities to scrape, as used in website URLs, e.g. "den-haag".
+    /// Local websites are only scraped for their own city.
+    pub cities: Vec<String>,
+    /// Only search for properties in this price range, on websites which support it.
+    pub min_price: Option<usize>,
+    pub max_price: Option<usize>,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        Self {
+            cities: vec!["rotterdam".to_string()],
+            min_price: None,
+            max_price: None,
+        }
+    }
+}
+
+/// When to consider a scraper broken.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HealthConfig {
+    /// Alert after this many scrapes in a row failed.
+    pub max_consecutive_failures: u32,
+    /// Alert when a website had no new listings for this long, unless overridden per scraper.
+    pub stale_after_hours: u64,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            max_consecutive_failures: 3,
+            stale_after_hours: 48,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FilterConfig {
+    pub min_price: Option<usize>,
+    pub max_price: Option<usize>,
+    pub min_area: Option<u32>,
+    pub min_rooms: Option<u32>,
+    /// "furnished", "upholstered" or "shell"
+    #[serde(deserialize_with = "deserialize_interior")]
+    pub interior: Option<Interior>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScraperConfig {
+    pub enabled: Option<bool>,
+    /// Overrides `search.cities` for this scraper.
+    pub cities: Option<Vec<String>>,
+    pub interval_secs: Option<u64>,
+    pub max_pages: Option<u32>,
+    pub stale_after_hours: Option<u64>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            database_url: "sqlite://database.db".to_string(),
+            admin_chat_id: None,
+            scrape_interval_secs: 300,
+            scrape_jitter_secs: 30,
+            scrape_timeout_secs: 180,
+            max_concurrent_scrapes: 2,
+            max_pages: 5,
+            http: HttpConfig::default(),
+            search: SearchConfig::default(),
+            health: HealthConfig::default(),
+            default_filter: FilterConfig::default(),
+            scrapers: HashMap::new(),
+            site_definitions: Vec::new(),
+            brokers: Vec::new(),
+            definitions: Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let raw = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read config file {}", path.display()))?;
+        let directory = path.parent().unwrap_or(Path::new("."));
+        Self::parse(&raw, directory)
+            .with_context(|| format!("invalid config file {}", path.display()))
+    }
+
+    /// Parse and validate a configuration, loading site definitions relative to `directory`.
+    fn parse(raw: &str, directory: &Path) -> anyhow::Result<Self> {
+        let mut config: Self = toml::from_str(raw)?;
+        for definition in config.site_definitions.iter() {
+            let definition = SelectorDefinition::load(&directory.join(definition))?;
+            config
+                .definitions
+                .push(Definition::Selector(Box::new(definition)));
+        }
+        config.definitions.extend(
+            config
+                .brokers
+                .iter()
+                .cloned()
+                .map(|broker| Definition::RealtimeListings(Box::new(broker))),
+        );
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            self.scrape_interval_secs > 0,
+            "scrape_interval_secs must be positive"
+        );
+        anyhow::ensure!(
+            self.scrape_timeout_secs > 0,
+            "scrape_timeout_secs must be positive"
+        );
+        anyhow::ensure!(
+            self.max_concurrent_scrapes > 0,
+            "max_concurrent_scrapes must be positive"
+        );
+        anyhow::ensure!(self.max_pages > 0, "max_pages must be positive");
+        anyhow::ensure!(
+            self.http.timeout_secs > 0,
+            "http.timeout_secs must be positive"
+        );
+        anyhow::ensure!(
+            self.health.max_consecutive_failures > 0,
+            "health.max_consecutive_failures must be positive"
+        );
+        anyhow::ensure!(
+            self.health.stale_after_hours > 0,
+            "health.stale_after_hours must be positive"
+        );
+
+        anyhow::ensure!(
+            !self.search.cities.is_empty(),
+            "search.cities must not be empty"
+        );
+        for city in self.all_cities() {
+            anyhow::ensure!(
+                is_slug(city),
+                "invalid city {city:?}, cities are written as in URLs, e.g. \"den-haag\""
+            );
+        }
+        if let (Some(min), Some(max)) = (self.search.min_price, self.search.max_price) {
+            anyhow::ensure!(
+                min <= max,
+                "search.min_price must not be larger than search.max_price"
+            );
+        }
+
+        for (i, definition) in self.definitions.iter().enumerate() {
+            let name = definition.name();
+            anyhow::ensure!(
+                matches!(Website::from_name(name), Website::Defined(_)),
+                "website {name} has the name of a built-in scraper"
+            );
+            anyhow::ensure!(
+                !name.is_empty() && is_slug(name),
+                "invalid website name {name:?}, use lowercase letters, digits and dashes"
+            );
+            anyhow::ensure!(
+                !self.definitions[..i]
+                    .iter()
+                    .any(|other| other.name() == name),
+                "there are multiple websites named {name}"
+            );
+        }
+        for broker in self.brokers.iter() {
+            broker.validate()?;
+            anyhow::ensure!(
+                is_slug(broker.city()),
+                "invalid city {:?} of broker {}, cities are written as in URLs, e.g. \"den-haag\"",
+                broker.city(),
+                broker.name()
+            );
+        }
+
+        for (website, scraper) in self.scrapers.iter() {
+            if let Website::Defined(name) = website {
+                anyhow::ensure!(
+                    self.definitions
+                        .iter()
+                        .any(|definition| definition.name() == name),
+                    "unknown scraper {name} in scrapers, it's not built in nor in site_definitions or brokers"
+                );
+            }
+            anyhow::ensure!(
+                scraper.interval_secs != Some(0),
+                "scrapers.{website}.interval_secs must be positive"
+            );
+            anyhow::ensure!(
+                scraper.max_pages != Some(0),
+                "scrapers.{website}.max_pages must be positive"
+            );
+            anyhow::ensure!(
+                scraper.stale_after_hours != Some(0),
+                "scrapers.{website}.stale_after_hours must be positive"
+            );
+        }
+
+        if let (Some(min), Some(max)) =
+            (self.default_filter.min_price, self.default_filter.max_price)
+        {
+            anyhow::ensure!(
+                min <= max,
+                "default_filter.min_price must not be larger than default_filter.max_price"
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Every website that can be scraped, the built-in ones and the defined ones.
+    pub(crate) fn websites(&self) -> Vec<Website> {
+        Website::BUILT_IN
+            .into_iter()
+            .chain(
+                self.definitions
+                    .iter()
+                    .map(|definition| Website::Defined(definition.name().to_string())),
+            )
+            .collect()
+    }
+
+    fn definition(&self, website: &Website) -> Option<&Definition> {
+        let Website::Defined(name) = website else {
+            return None;
+        };
+        self.definitions
+            .iter()
+            .find(|definition| definition.name() == name)
+    }
+
+    pub(crate) fn is_enabled(&self, website: &Website) -> bool {
+        self.scrapers
+            .get(website)
+            .and_then(|scraper| scraper.enabled)
+            .unwrap_or(true)
+    }
+
+    /// The cities to scrape `website` for.
+    pub(crate) fn cities(&self, website: &Website) -> Vec<&str> {
+        let cities = self
+            .scrapers
+            .get(website)
+            .and_then(|scraper| scraper.cities.as_ref())
+            .unwrap_or(&self.search.cities);
+        cities
+            .iter()
+            .map(String::as_str)
+            .filter(|city| {
+                website.covers(city)
+                    && self
+                        .definition(website)
+                        .is_none_or(|definition| definition.covers(city))
+            })
+            .collect()
+    }
+
+    /// Every city that is scraped by any scraper, so subscribers can follow it.
+    pub(crate) fn all_cities(&self) -> Vec<&str> {
+        let mut cities: Vec<&str> = self
+            .search
+            .cities
+            .iter()
+            .chain(
+                self.scrapers
+                    .values()
+                    .flat_map(|scraper| scraper.cities.iter().flatten()),
+            )
+            .map(String::as_str)
+            .collect();
+        cities.sort();
+        cities.dedup();
+        cities
+    }
+
+    pub(crate) fn search(&self, city: &str) -> Search {
+        Search {
+            city: city.to_string(),
+            min_price: self.search.min_price,
+            max_price: self.search.max_price,
+        }
+    }
+
+    pub(crate) fn scrape_interval(&self, website: &Website) -> Duration {
+        let secs = self
+            .scrapers
+            .get(website)
+            .and_then(|scraper| scraper.interval_secs)
+            .unwrap_or(self.scrape_interval_secs);
+        Duration::from_secs(secs)
+    }
+
+    pub(crate) fn max_pages(&self, website: &Website) -> u32 {
+        self.scrapers
+            .get(website)
+            .and_then(|scraper| scraper.max_pages)
+            .unwrap_or(self.max_pages)
+    }
+
+    /// A random delay to add to the scrape interval.
+    pub(crate) fn scrape_jitter(&self) -> Duration {
+        let millis = rand::thread_rng().gen_range(0..=self.scrape_jitter_secs * 1000);
+        Duration::from_millis(millis)
+    }
+
+    pub(crate) fn scrape_timeout(&self) -> Duration {
+        Duration::from_secs(self.scrape_timeout_secs)
+    }
+
+    /// How long `website` may go without new listings before it's considered broken.
+    pub(crate) fn stale_after(&self, website: &Website) -> Duration {
+        let hours = self
+            .scrapers
+            .get(website)
+            .and_then(|scraper| scraper.stale_after_hours)
+            .unwrap_or(self.health.stale_after_hours);
+        Duration::from_secs(hours * 60 * 60)
+    }
+
+    pub(crate) fn default_filter(&self) -> Filter {
+        Filter {
+            min_price: self.default_filter.min_price,
+            max_price: self.default_filter.max_price,
+            min_area: self.default_filter.min_area,
+            min_rooms: self.default_filter.min_rooms,
+            interior: self.default_filter.interior,
+        }
+    }
+}
+
+/// Whether `name` is written as in URLs, e.g. "den-haag".
+fn is_slug(name: &str) -> bool {
+    name.chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+}
+
+fn deserialize_interior<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Interior>, D::Error> {
+    let raw: Option<String> = Option::deserialize(deserializer)?;
+    raw.map(|raw| raw.parse().map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(raw: &str) -> anyhow::Result<Config> {
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/selector");
+        Config::parse(raw, &fixtures)
+    }
+
+    #[test]
+    fn empty_config_has_defaults() {
+        let config = parse("").unwrap();
+
+        assert_eq!(config.database_url, "sqlite://database.db");
+        assert_eq!(config.http.max_retries, 3);
+        assert_eq!(config.websites(), Website::BUILT_IN.to_vec());
+        assert!(config.is_enabled(&Website::Pararius));
+        assert_eq!(config.cities(&Website::Pararius), vec!["rotterdam"]);
+        assert_eq!(config.all_cities(), vec!["rotterdam"]);
+        assert_eq!(
+            config.scrape_interval(&Website::Pararius),
+            Duration::from_secs(300)
+        );
+        assert_eq!(config.max_pages(&Website::Pararius), 5);
+        assert_eq!(
+            config.stale_after(&Website::Pararius),
+            Duration::from_secs(48 * 60 * 60)
+        );
+        assert_eq!(config.default_filter().max_price, None);
+    }
+
+    #[test]
+    fn scrapers_override_defaults() {
+        let config = parse(
+            r#"
+            [search]
+            cities = ["utrecht", "rotterdam"]
+
+            [default_filter]
+            max_price = 1799
+            interior = "furnished"
+
+            [scrapers.pararius]
+            cities = ["den-haag", "utrecht"]
+            interval_secs = 600
+            max_pages = 10
+
+            [scrapers.verra]
+            enabled = false
+            stale_after_hours = 168
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.cities(&Website::Pararius),
+            vec!["den-haag", "utrecht"]
+        );
+        assert_eq!(
+            config.cities(&Website::Huurwoningen),
+            vec!["utrecht", "rotterdam"]
+        );
+        // Local websites are only scraped for their own city
+        assert_eq!(config.cities(&Website::RotterdamWonen), vec!["rotterdam"]);
+        assert_eq!(
+            config.all_cities(),
+            vec!["den-haag", "rotterdam", "utrecht"]
+        );
+        assert_eq!(
+            config.scrape_interval(&Website::Pararius),
+            Duration::from_secs(600)
+        );
+        assert_eq!(config.max_pages(&Website::Pararius), 10);
+        assert_eq!(config.max_pages(&Website::Huurwoningen), 5);
+        assert!(!config.is_enabled(&Website::Verra));
+        assert_eq!(
+            config.stale_after(&Website::Verra),
+            Duration::from_secs(168 * 60 * 60)
+        );
+        assert_eq!(config.default_filter().max_price, Some(1799));
+        assert_eq!(config.default_filter().interior, Some(Interior::Furnished));
+    }
+
+    #[test]
+    fn loads_defined_websites() {
+        let config = parse(
+            r#"
+            site_definitions = ["definition.toml"]
+
+            [search]
+            cities = ["rotterdam", "utrecht"]
+
+            [[brokers]]
+            name = "havenstad"
+            base_url = "https://www.havenstadwonen.nl"
+            city = "rotterdam"
+
+            [scrapers.maasstad]
+            max_pages = 2
+            "#,
+        )
+        .unwrap();
+
+        let maasstad = Website::Defined("maasstad".to_string());
+        let havenstad = Website::Defined("havenstad".to_string());
+        assert!(config
+            .websites()
+            .ends_with(&[maasstad.clone(), havenstad.clone()]));
+        assert_eq!(config.max_pages(&maasstad), 2);
+        assert_eq!(config.cities(&maasstad), vec!["rotterdam", "utrecht"]);
+        // The feed of a broker has no search
+        assert_eq!(config.cities(&havenstad), vec!["rotterdam"]);
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        for raw in [
+            "scrape_interval_secs = 0",
+            "max_pages = 0",
+            "unknown = true",
+            "[http]\ntimeout_secs = 0",
+            "[search]\ncities = []",
+            "[search]\ncities = [\"Den Haag\"]",
+            "[search]\nmin_price = 2000\nmax_price = 1000",
+            "[default_filter]\ninterior = \"cozy\"",
+            "[default_filter]\nmin_price = 2000\nmax_price = 1000",
+            "[scrapers.pararius]\ninterval_secs = 0",
+            "[scrapers.pararius]\nmax_pages = 0",
+            "[scrapers.verra]\nstale_after_hours = 0",
+            "[scrapers.maasstad]\nenabled = true",
+            "site_definitions = [\"missing.toml\"]",
+            "[[brokers]]\nname = \"verra\"\nbase_url = \"https://www.verra.nl\"\ncity = \"rotterdam\"",
+            "[[brokers]]\nname = \"havenstad\"\nbase_url = \"havenstad\"\ncity = \"rotterdam\"",
+        ] {
+            assert!(parse(raw).is_err(), "{raw}");
+        }
+    }
+}
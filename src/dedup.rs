@@ -0,0 +1,206 @@
+use geo::{Distance, Haversine, Point};
+
+use crate::scraping::PRICE_ON_REQUEST;
+
+/// Properties listed on different websites are only considered the same if their
+/// location is this close (in meters), unless their address or postcode matches.
+const MAX_DISTANCE: f64 = 50.0;
+/// Websites round the area differently, or measure it differently altogether.
+const MAX_AREA_DIFFERENCE: u32 = 3;
+/// Brokers sometimes list a property slightly more expensive than the aggregator sites do.
+const MAX_PRICE_DIFFERENCE: f64 = 0.05;
+
+/// The parts of a property used to recognize it when it's listed on multiple websites.
+#[derive(Debug, Clone)]
+pub(super) struct Fingerprint {
+    /// Normalized street and house number, if known
+    address: Option<String>,
+    postcode: Option<String>,
+    area: u32,
+    /// Unknown if the price is on request
+    price: Option<usize>,
+    location: Option<Point>,
+}
+
+impl Fingerprint {
+    pub(super) fn new(
+        title: &str,
+        postcode: Option<&str>,
+        area: u32,
+        price: usize,
+        location: Option<Point>,
+    ) -> Self {
+        Self {
+            address: normalize_address(title),
+            postcode: postcode.map(|postcode| postcode.replace(' ', "").to_uppercase()),
+            area,
+            price: (price != PRICE_ON_REQUEST).then_some(price),
+            location,
+        }
+    }
+
+    /// The area range a duplicate of this property must be in, for querying candidates.
+    pub(super) fn area_range(&self) -> (u32, u32) {
+        (
+            self.area.saturating_sub(MAX_AREA_DIFFERENCE),
+            self.area + MAX_AREA_DIFFERENCE,
+        )
+    }
+
+    pub(super) fn is_duplicate_of(&self, other: &Fingerprint) -> bool {
+        if self.area.abs_diff(other.area) > MAX_AREA_DIFFERENCE {
+            return false;
+        }
+
+        if let (Some(price), Some(other_price)) = (self.price, other.price) {
+            let cheapest = price.min(other_price) as f64;
+            if price.abs_diff(other_price) as f64 > cheapest * MAX_PRICE_DIFFERENCE {
+                return false;
+            }
+        }
+
+        if let (Some(address), Some(other_address)) = (&self.address, &other.address) {
+            if address == other_address {
+                return true;
+            }
+            // Websites write addresses differently, e.g. "Tweede" or "2e", but new buildings
+            // often have lots of identical apartments with the same postcode, so the house
+            // numbers have to match at least.
+            if house_number(address) != house_number(other_address) {
+                return false;
+            }
+        }
+
+        if let (Some(postcode), Some(other_postcode)) = (&self.postcode, &other.postcode) {
+            if postcode == other_postcode {
+                return true;
+            }
+        }
+
+        if let (Some(location), Some(other_location)) = (self.location, other.location) {
+            return Haversine::distance(location, other_location) <= MAX_DISTANCE;
+        }
+
+        false
+    }
+}
+
+/// Reduce a title like "Flat Kralingse Plaslaan 12-B" to "kralingseplaslaan12b".
+/// Titles without a house number aren't specific enough to be an address.
+fn normalize_address(title: &str) -> Option<String> {
+    let title = title.to_lowercase();
+    // Some websites prefix the address with the type of property
+    let title = [
+        "flat ",
+        "apartment ",
+        "appartement ",
+        "house ",
+        "huis ",
+        "studio ",
+        "room ",
+    ]
+    .iter()
+    .find_map(|prefix| title.strip_prefix(prefix))
+    .unwrap_or(&title);
+
+    let address: String = title.chars().filter(|c| c.is_alphanumeric()).collect();
+    address
+        .contains(|c: char| c.is_ascii_digit())
+        .then_some(address)
+}
+
+/// The first number after the street name of a normalized address, e.g. "14" for
+/// "2emiddellandstraat14a".
+fn house_number(address: &str) -> Option<&str> {
+    let start = address
+        .char_indices()
+        .skip_while(|(_, c)| !c.is_alphabetic())
+        .find(|(_, c)| c.is_ascii_digit())?
+        .0;
+    let number = &address[start..];
+    let end = number
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(number.len());
+    Some(&number[..end])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fingerprint(
+        title: &str,
+        postcode: Option<&str>,
+        location: Option<(f64, f64)>,
+    ) -> Fingerprint {
+        let location = location.map(|(longitude, latitude)| Point::new(longitude, latitude));
+        Fingerprint::new(title, postcode, 75, 1495, location)
+    }
+
+    #[test]
+    fn normalizes_addresses() {
+        assert_eq!(
+            normalize_address("Flat Kralingse Plaslaan 12-B").as_deref(),
+            Some("kralingseplaslaan12b")
+        );
+        assert_eq!(
+            normalize_address("Bergweg 55 b").as_deref(),
+            Some("bergweg55b")
+        );
+        // Not specific enough
+        assert_eq!(normalize_address("Studio Schiedamseweg"), None);
+
+        assert_eq!(house_number("2emiddellandstraat14a"), Some("14"));
+        assert_eq!(house_number("bergweg55b"), Some("55"));
+    }
+
+    #[test]
+    fn matching_addresses_are_duplicates() {
+        let verra = fingerprint("Statenweg 120", Some("3039 JK"), None);
+        let funda = fingerprint("Appartement Statenweg 120", None, None);
+        assert!(funda.is_duplicate_of(&verra));
+    }
+
+    #[test]
+    fn differently_written_addresses_match_on_postcode() {
+        let pararius = fingerprint("Flat 2e Middellandstraat 14", Some("3014 EG"), None);
+        let funda = fingerprint("Tweede Middellandstraat 14 A", Some("3014eg"), None);
+        assert!(funda.is_duplicate_of(&pararius));
+
+        // Without a house number only the postcode is left to compare
+        let huurstunt = fingerprint("Appartement Middellandstraat", Some("3014 EG"), None);
+        assert!(huurstunt.is_duplicate_of(&pararius));
+    }
+
+    #[test]
+    fn nearby_properties_are_duplicates() {
+        let verra = fingerprint("Statenweg 120", None, Some((4.4662, 51.9316)));
+        // About 20 m away
+        let kamernet = fingerprint("Statenweg", None, Some((4.4664, 51.9317)));
+        assert!(kamernet.is_duplicate_of(&verra));
+
+        // About 140 m away
+        let other = fingerprint("Statenweg", None, Some((4.4682, 51.9316)));
+        assert!(!other.is_duplicate_of(&verra));
+    }
+
+    #[test]
+    fn different_properties_are_not_duplicates() {
+        // Identical apartments in the same building
+        let first = fingerprint("Wijnhaven 10", Some("3011 WH"), Some((4.4880, 51.9180)));
+        let second = fingerprint("Wijnhaven 12", Some("3011 WH"), Some((4.4880, 51.9180)));
+        assert!(!second.is_duplicate_of(&first));
+
+        let smaller = Fingerprint::new("Wijnhaven 10", Some("3011 WH"), 60, 1495, None);
+        assert!(!smaller.is_duplicate_of(&first));
+        let pricier = Fingerprint::new("Wijnhaven 10", Some("3011 WH"), 75, 1650, None);
+        assert!(!pricier.is_duplicate_of(&first));
+    }
+
+    #[test]
+    fn price_on_request_matches_any_price() {
+        let pararius = fingerprint("Statenweg 120", Some("3039 JK"), None);
+        let broker = Fingerprint::new("Statenweg 120", None, 75, PRICE_ON_REQUEST, None);
+        assert!(broker.is_duplicate_of(&pararius));
+    }
+}
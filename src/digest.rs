@@ -0,0 +1,229 @@
+use std::fmt;
+
+use chrono::{DateTime, Days, NaiveTime, Timelike, Utc};
+use itertools::Itertools;
+
+use crate::{
+    notification::{describe_rent, price_per_m2},
+    persistence::QueuedProperty,
+};
+
+/// Digests list at most this many properties, to stay within the message size limit.
+/// The ones with the highest price per m² are left out.
+const MAX_DIGEST_PROPERTIES: usize = 25;
+
+/// How a subscriber gets notified about properties.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(super) enum Delivery {
+    /// A message for every property, as soon as it's found.
+    Instant,
+    /// A summary of the properties found in the past hour, at the start of every hour.
+    Hourly,
+    /// A summary of the properties found in the past day, every day at this time (UTC).
+    Daily(NaiveTime),
+}
+
+impl Delivery {
+    /// Parse "instant", "hourly" or "daily 08:00", as used in the /delivery command.
+    pub(super) fn parse(text: &str) -> Option<Self> {
+        let mut words = text.split_whitespace();
+        let mode = words.next()?.to_lowercase();
+        let time = words.next();
+        if words.next().is_some() {
+            return None;
+        }
+
+        match (mode.as_str(), time) {
+            ("instant", None) => Some(Delivery::Instant),
+            ("hourly", None) => Some(Delivery::Hourly),
+            ("daily", Some(time)) => parse_time(time).map(Delivery::Daily),
+            // Mornings are the most common choice
+            ("daily", None) => Some(Delivery::Daily(NaiveTime::from_hms_opt(8, 0, 0)?)),
+            _ => None,
+        }
+    }
+
+    /// Restore the delivery stored as its mode and the time of daily digests.
+    pub(super) fn from_stored(mode: &str, time: Option<&str>) -> Option<Self> {
+        match (mode, time) {
+            ("instant", _) => Some(Delivery::Instant),
+            ("hourly", _) => Some(Delivery::Hourly),
+            ("daily", Some(time)) => parse_time(time).map(Delivery::Daily),
+            _ => None,
+        }
+    }
+
+    pub(super) fn mode(&self) -> &'static str {
+        match self {
+            Delivery::Instant => "instant",
+            Delivery::Hourly => "hourly",
+            Delivery::Daily(_) => "daily",
+        }
+    }
+
+    /// The time of daily digests as HH:MM.
+    pub(super) fn time(&self) -> Option<String> {
+        match self {
+            Delivery::Daily(time) => Some(time.format("%H:%M").to_string()),
+            _ => None,
+        }
+    }
+
+    /// Whether the next digest is due, if the last one was sent at `last_sent`.
+    /// With instant delivery anything still queued is sent right away.
+    pub(super) fn is_due(&self, last_sent: i64, now: DateTime<Utc>) -> bool {
+        match self.last_scheduled(now) {
+            Some(scheduled) => scheduled.timestamp() > last_sent,
+            None => true,
+        }
+    }
+
+    /// When the most recent digest up to `now` was scheduled, `None` with instant delivery.
+    fn last_scheduled(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self {
+            Delivery::Instant => None,
+            Delivery::Hourly => now.with_minute(0)?.with_second(0)?.with_nanosecond(0),
+            Delivery::Daily(time) => {
+                let today = now.date_naive().and_time(*time).and_utc();
+                if today <= now {
+                    Some(today)
+                } else {
+                    today.checked_sub_days(Days::new(1))
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Delivery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Delivery::Instant => f.write_str("instantly"),
+            Delivery::Hourly => f.write_str("in an hourly digest"),
+            Delivery::Daily(time) => write!(f, "in a daily digest at {} UTC", time.format("%H:%M")),
+        }
+    }
+}
+
+/// Parse "08:00" or just "8".
+fn parse_time(text: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(text, "%H:%M")
+        .ok()
+        .or_else(|| NaiveTime::from_hms_opt(text.parse().ok()?, 0, 0))
+}
+
+/// Summarize the queued properties in one message, the best value for money first.
+pub(super) fn describe_digest(mut properties: Vec<QueuedProperty>) -> String {
+    // Properties without a price or area go last
+    properties.sort_by(|a, b| {
+        let a = price_per_m2(a.price, a.area).unwrap_or(f64::INFINITY);
+        let b = price_per_m2(b.price, b.area).unwrap_or(f64::INFINITY);
+        a.total_cmp(&b)
+    });
+
+    let header = match properties.len() {
+        1 => "1 new property".to_string(),
+        count => format!("{count} new properties"),
+    };
+    let listed = properties
+        .iter()
+        .take(MAX_DIGEST_PROPERTIES)
+        .map(|property| {
+            let mut line = format!(
+                "{}\n{}, {} m²",
+                property.title,
+                describe_rent(property.price),
+                property.area
+            );
+            if let Some(price_per_m2) = price_per_m2(property.price, property.area) {
+                line.push_str(&format!(" (€{price_per_m2:.2} per m²)"));
+            }
+            if let Some(rooms) = property.rooms {
+                line.push_str(&format!(", {rooms} rooms"));
+            }
+            format!("{line}\n{}", property.url)
+        })
+        .join("\n\n");
+
+    let mut text = format!("{header}, cheapest per m² first:\n\n{listed}");
+    if properties.len() > MAX_DIGEST_PROPERTIES {
+        text.push_str(&format!(
+            "\n\nAnd {} more",
+            properties.len() - MAX_DIGEST_PROPERTIES
+        ));
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scraping::PRICE_ON_REQUEST;
+
+    fn at(time: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(time).unwrap().to_utc()
+    }
+
+    #[test]
+    fn parses_delivery() {
+        let eight = NaiveTime::from_hms_opt(8, 0, 0).unwrap();
+        assert_eq!(Delivery::parse("instant"), Some(Delivery::Instant));
+        assert_eq!(Delivery::parse("Hourly"), Some(Delivery::Hourly));
+        assert_eq!(Delivery::parse("daily"), Some(Delivery::Daily(eight)));
+        assert_eq!(Delivery::parse("daily 8"), Some(Delivery::Daily(eight)));
+        assert_eq!(
+            Delivery::parse("daily 18:30"),
+            Some(Delivery::Daily(NaiveTime::from_hms_opt(18, 30, 0).unwrap()))
+        );
+        assert_eq!(Delivery::parse("daily 25:00"), None);
+        assert_eq!(Delivery::parse("hourly 8"), None);
+        assert_eq!(Delivery::parse("weekly"), None);
+
+        let daily = Delivery::Daily(eight);
+        assert_eq!(
+            Delivery::from_stored(daily.mode(), daily.time().as_deref()),
+            Some(daily)
+        );
+    }
+
+    #[test]
+    fn digests_are_due_once_per_period() {
+        let sent = at("2025-04-06T10:05:00Z").timestamp();
+
+        assert!(!Delivery::Hourly.is_due(sent, at("2025-04-06T10:59:00Z")));
+        assert!(Delivery::Hourly.is_due(sent, at("2025-04-06T11:00:00Z")));
+
+        let daily = Delivery::Daily(NaiveTime::from_hms_opt(8, 0, 0).unwrap());
+        assert!(!daily.is_due(sent, at("2025-04-07T07:59:00Z")));
+        assert!(daily.is_due(sent, at("2025-04-07T08:00:00Z")));
+        // A digest missed while the bot was down is sent when it's back
+        assert!(daily.is_due(sent, at("2025-04-08T07:00:00Z")));
+
+        assert!(Delivery::Instant.is_due(sent, at("2025-04-06T10:06:00Z")));
+    }
+
+    #[test]
+    fn sorts_by_price_per_m2() {
+        let property = |title: &str, price, area| QueuedProperty {
+            title: title.to_string(),
+            url: format!("https://example.com/{title}"),
+            price,
+            area,
+            rooms: None,
+        };
+        let text = describe_digest(vec![
+            property("expensive", 2000, 50),
+            property("unknown", 900, 0),
+            property("cheap", 1500, 75),
+            property("on request", PRICE_ON_REQUEST, 80),
+        ]);
+
+        let titles: Vec<_> = ["expensive", "unknown", "cheap", "on request"]
+            .into_iter()
+            .map(|title| text.find(&format!("{title}\n")).unwrap())
+            .collect();
+        assert!(titles[2] < titles[0] && titles[0] < titles[1] && titles[0] < titles[3]);
+        assert!(text.contains("on request\nPrice on request, 80 m²\n"));
+        assert!(text.starts_with("4 new properties, cheapest per m² first"));
+    }
+}
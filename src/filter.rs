@@ -0,0 +1,143 @@
+use crate::scraping::{Interior, PartialScrapeResult, PRICE_ON_REQUEST};
+
+/// The search criteria of a single subscriber.
+/// All limits are inclusive, and `None` means the subscriber doesn't care.
+#[derive(Debug, Clone, Default)]
+pub(super) struct Filter {
+    pub(super) min_price: Option<usize>,
+    pub(super) max_price: Option<usize>,
+    pub(super) min_area: Option<u32>,
+    pub(super) min_rooms: Option<u32>,
+    pub(super) interior: Option<Interior>,
+}
+
+impl Filter {
+    pub(super) fn matches(&self, property: &PartialScrapeResult) -> bool {
+        // A price on request is fine by any minimum price, but not by a maximum price
+        let on_request = property.price == PRICE_ON_REQUEST;
+        if !on_request && self.min_price.is_some_and(|min| property.price < min) {
+            return false;
+        }
+
+        if self
+            .max_price
+            .is_some_and(|max| on_request || property.price > max)
+        {
+            return false;
+        }
+
+        if self.min_area.is_some_and(|min| property.area < min) {
+            return false;
+        }
+
+        // If the website doesn't tell us the number of rooms we'd rather send it
+        // than miss out on it.
+        if let (Some(min), Some(rooms)) = (self.min_rooms, property.rooms) {
+            if rooms < min {
+                return false;
+            }
+        }
+
+        if let (Some(wanted), Some(interior)) = (self.interior, property.interior) {
+            if wanted != interior {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn property() -> PartialScrapeResult {
+        PartialScrapeResult {
+            title: "Kralingse Plaslaan 12".to_string(),
+            price: 1500,
+            url: "https://pararius.com/apartment-for-rent/rotterdam/1a2b3c4d/kralingse-plaslaan"
+                .to_string(),
+            area: 60,
+            rooms: Some(3),
+            interior: Some(Interior::Upholstered),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn matches_everything_without_limits() {
+        assert!(Filter::default().matches(&property()));
+    }
+
+    #[test]
+    fn limits_are_inclusive() {
+        let filter = Filter {
+            min_price: Some(1500),
+            max_price: Some(1500),
+            min_area: Some(60),
+            min_rooms: Some(3),
+            interior: Some(Interior::Upholstered),
+        };
+        assert!(filter.matches(&property()));
+    }
+
+    #[test]
+    fn rejects_properties_outside_limits() {
+        let rejects = |filter: Filter| !filter.matches(&property());
+
+        assert!(rejects(Filter {
+            min_price: Some(1501),
+            ..Default::default()
+        }));
+        assert!(rejects(Filter {
+            max_price: Some(1499),
+            ..Default::default()
+        }));
+        assert!(rejects(Filter {
+            min_area: Some(61),
+            ..Default::default()
+        }));
+        assert!(rejects(Filter {
+            min_rooms: Some(4),
+            ..Default::default()
+        }));
+        assert!(rejects(Filter {
+            interior: Some(Interior::Furnished),
+            ..Default::default()
+        }));
+    }
+
+    #[test]
+    fn matches_unknown_details() {
+        let filter = Filter {
+            min_rooms: Some(4),
+            interior: Some(Interior::Furnished),
+            ..Default::default()
+        };
+        let property = PartialScrapeResult {
+            rooms: None,
+            interior: None,
+            ..property()
+        };
+        assert!(filter.matches(&property));
+    }
+
+    #[test]
+    fn matches_price_on_request_without_maximum_price() {
+        let property = PartialScrapeResult {
+            price: PRICE_ON_REQUEST,
+            ..property()
+        };
+        assert!(Filter {
+            min_price: Some(1000),
+            ..Default::default()
+        }
+        .matches(&property));
+        assert!(!Filter {
+            max_price: Some(PRICE_ON_REQUEST),
+            ..Default::default()
+        }
+        .matches(&property));
+    }
+}
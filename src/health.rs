@@ -0,0 +1,200 @@
+use std::{ops::AddAssign, time::Duration};
+
+use crate::scraping::Website;
+
+/// What a successful scrape found.
+#[derive(Debug, Clone, Copy, Default)]
+pub(super) struct ScrapeStats {
+    pub(super) listings: usize,
+    /// Listings which couldn't be parsed, and aren't part of `listings`.
+    pub(super) parse_errors: usize,
+    /// Listings we had never seen before.
+    pub(super) new_listings: usize,
+}
+
+impl AddAssign for ScrapeStats {
+    fn add_assign(&mut self, other: Self) {
+        self.listings += other.listings;
+        self.parse_errors += other.parse_errors;
+        self.new_listings += other.new_listings;
+    }
+}
+
+/// A single scrape of a website.
+pub(super) struct ScrapeRun {
+    pub(super) started_at: i64,
+    pub(super) finished_at: i64,
+    pub(super) outcome: Result<ScrapeStats, String>,
+}
+
+/// The runs of a scraper relevant for judging its health.
+pub(super) struct ScrapeHistory {
+    /// The outcomes of the most recent runs, newest first.
+    pub(super) recent: Vec<Result<ScrapeStats, String>>,
+    pub(super) first_run_at: Option<i64>,
+    /// When the most recent run finished.
+    pub(super) last_run_at: Option<i64>,
+    pub(super) last_new_listing_at: Option<i64>,
+}
+
+/// How a scraper is doing, judged by its recent runs.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum Health {
+    Healthy,
+    /// The last `failures` runs failed, the latest one with `error`.
+    Failing {
+        failures: usize,
+        error: String,
+    },
+    /// The last run succeeded, but didn't find a single listing.
+    /// Usually this means the website changed its layout.
+    Empty,
+    /// No new listings were found since `since`.
+    Stale {
+        since: i64,
+    },
+}
+
+impl Health {
+    pub(super) fn assess(
+        history: &ScrapeHistory,
+        now: i64,
+        max_failures: usize,
+        stale_after: Duration,
+    ) -> Self {
+        let failures = history
+            .recent
+            .iter()
+            .take_while(|outcome| outcome.is_err())
+            .count();
+        if failures > 0 && failures >= max_failures {
+            let error = history.recent[0].clone().unwrap_err();
+            return Health::Failing { failures, error };
+        }
+
+        let last_success = history
+            .recent
+            .iter()
+            .find_map(|outcome| outcome.as_ref().ok());
+        if last_success.is_some_and(|stats| stats.listings == 0) {
+            return Health::Empty;
+        }
+
+        // A scraper which never found anything new is stale since it first ran
+        let since = history.last_new_listing_at.or(history.first_run_at);
+        if let Some(since) = since {
+            if now - since > stale_after.as_secs() as i64 {
+                return Health::Stale { since };
+            }
+        }
+
+        Health::Healthy
+    }
+
+    /// Whether going from `previous` to this health is worth telling the admin about.
+    /// Only changes in the kind of problem count, not e.g. one more failure.
+    pub(super) fn changed_from(&self, previous: &Health) -> bool {
+        std::mem::discriminant(self) != std::mem::discriminant(previous)
+    }
+
+    /// The message for the admin when the scraper of `website` got into this state.
+    pub(super) fn describe(&self, website: &Website, now: i64) -> String {
+        match self {
+            Health::Healthy => format!("Scraper {website} is working again"),
+            Health::Failing { failures, error } => {
+                format!("Scraper {website} failed {failures} times in a row, last error: {error}")
+            }
+            Health::Empty => format!(
+                "Scraper {website} found no listings at all, the website may have changed its layout"
+            ),
+            Health::Stale { since } => format!(
+                "Scraper {website} hasn't found new listings for {} hours",
+                (now - since) / 3600
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: i64 = 24 * 60 * 60;
+
+    fn stats(listings: usize, new_listings: usize) -> Result<ScrapeStats, String> {
+        Ok(ScrapeStats {
+            listings,
+            parse_errors: 0,
+            new_listings,
+        })
+    }
+
+    fn assess(
+        recent: Vec<Result<ScrapeStats, String>>,
+        last_new_listing_at: Option<i64>,
+    ) -> Health {
+        let history = ScrapeHistory {
+            recent,
+            first_run_at: Some(0),
+            last_run_at: Some(10 * DAY),
+            last_new_listing_at,
+        };
+        Health::assess(&history, 10 * DAY, 3, Duration::from_secs(2 * DAY as u64))
+    }
+
+    #[test]
+    fn failing_after_max_failures() {
+        let error = || Err("no element matching price".to_string());
+
+        assert_eq!(
+            assess(vec![error(), error(), stats(10, 1)], Some(10 * DAY)),
+            Health::Healthy
+        );
+        assert_eq!(
+            assess(
+                vec![error(), error(), error(), stats(10, 1)],
+                Some(10 * DAY)
+            ),
+            Health::Failing {
+                failures: 3,
+                error: "no element matching price".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn empty_when_nothing_listed() {
+        assert_eq!(assess(vec![stats(0, 0)], Some(10 * DAY)), Health::Empty);
+        // A failure doesn't make the website non-empty
+        assert_eq!(
+            assess(
+                vec![Err("timed out".to_string()), stats(0, 0)],
+                Some(10 * DAY)
+            ),
+            Health::Empty
+        );
+    }
+
+    #[test]
+    fn stale_without_new_listings() {
+        assert_eq!(
+            assess(vec![stats(10, 0)], Some(7 * DAY)),
+            Health::Stale { since: 7 * DAY }
+        );
+        assert_eq!(assess(vec![stats(10, 0)], Some(9 * DAY)), Health::Healthy);
+        assert_eq!(assess(vec![stats(10, 0)], None), Health::Stale { since: 0 });
+    }
+
+    #[test]
+    fn only_new_problems_are_changes() {
+        let failing = |failures| Health::Failing {
+            failures,
+            error: "timed out".to_string(),
+        };
+
+        assert!(failing(3).changed_from(&Health::Healthy));
+        assert!(!failing(4).changed_from(&failing(3)));
+        assert!(Health::Empty.changed_from(&failing(3)));
+        assert!(Health::Healthy.changed_from(&Health::Empty));
+    }
+}
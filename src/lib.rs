@@ -1,39 +1,123 @@
+pub mod config;
+mod dedup;
+mod digest;
+mod filter;
+mod health;
 mod location;
+mod notification;
 mod persistence;
 pub mod scraping;
+mod wizard;
 
-use std::sync::Arc;
+use std::{
+    collections::HashMap,
+    path::Path,
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
 
-use geo::Contains;
-use location::DESIRED_LOCATION;
-use persistence::Persistence;
+use config::Config;
+use dedup::Fingerprint;
+use digest::Delivery;
+use filter::Filter;
+use health::{Health, ScrapeHistory, ScrapeRun, ScrapeStats};
+use itertools::Itertools;
+use location::{PointOfInterest, Region};
+use notification::{describe_rent, ListingEvent, PropertyAction};
+use persistence::{KnownProperty, Persistence, PropertyStatus};
 use scraping::{
-    huurwoningen::HuurwoningenScraper, pararius::ParariusScraper,
-    rotterdamwonen::RotterdamWonenScraper, verra::VerraMakelaarsScraper, vesteda::VestedaScraper,
-    WebsiteScraper,
+    http::HttpClient, FullScrapeResult, Interior, Listings, ScrapeResult, Website, WebsiteScraper,
 };
 use teloxide::{
     dispatching::{HandlerExt, UpdateFilterExt},
+    net::Download,
     prelude::*,
+    types::{InlineKeyboardMarkup, InputFile, MessageId, ReplyParameters},
     utils::command::BotCommands,
 };
+use tokio::sync::Semaphore;
+use wizard::Wizard;
+
+/// GeoJSON files larger than this are rejected, no reasonable region needs that much detail.
+const MAX_REGION_FILE_SIZE: u32 = 1024 * 1024;
+/// How often to check whether digests are due.
+const DIGEST_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+/// Only the most recent tracked properties are listed, to stay within the message size limit.
+const MAX_TRACKED_LISTED: usize = 20;
 
 #[derive(BotCommands, Clone)]
 #[command(rename_rule = "lowercase")]
 enum Command {
     #[command(description = "Intro message")]
     Start,
+    #[command(description = "Stop the questions of /start")]
+    Cancel,
     #[command(description = "Subscribe to new properties")]
     Subscribe,
+    #[command(description = "Unsubscribe, forgetting all your settings")]
+    Unsubscribe,
+    #[command(
+        description = "Pause notifications, e.g. /pause 3d for three days (h, d or w), or without a duration until /resume"
+    )]
+    Pause(String),
+    #[command(description = "Resume paused notifications")]
+    Resume,
+    #[command(
+        description = "Get properties instantly, or in a digest: /delivery instant, /delivery hourly or /delivery daily 08:00 (UTC)"
+    )]
+    Delivery(String),
+    #[command(description = "Show your subscription, filters and regions")]
+    Status,
+    #[command(description = "Set the minimum monthly rent, e.g. /setminprice 1000")]
+    SetMinPrice(String),
+    #[command(description = "Set the maximum monthly rent, e.g. /setmaxprice 1500")]
+    SetMaxPrice(String),
+    #[command(description = "Set the minimum area in m², e.g. /setminarea 55")]
+    SetMinArea(String),
+    #[command(description = "Set the minimum number of rooms, e.g. /setminrooms 2")]
+    SetMinRooms(String),
+    #[command(
+        description = "Only get furnished, upholstered or shell properties, e.g. /setinterior furnished"
+    )]
+    SetInterior(String),
+    #[command(
+        description = "Reply to a location with /addregion <radius in km> <name> to add a region. You can also send a GeoJSON file with the region name as caption"
+    )]
+    AddRegion(String),
+    #[command(description = "List your regions")]
+    Regions,
+    #[command(description = "Remove a region, e.g. /removeregion kralingen")]
+    RemoveRegion(String),
+    #[command(
+        description = "Reply to a location with /addpoi <max distance in km> <name> to only get properties close to it"
+    )]
+    AddPoi(String),
+    #[command(description = "List your points of interest")]
+    Pois,
+    #[command(description = "Remove a point of interest, e.g. /removepoi office")]
+    RemovePoi(String),
+    #[command(description = "Get properties in a city, e.g. /follow utrecht")]
+    Follow(String),
+    #[command(description = "Stop getting properties in a city, e.g. /unfollow utrecht")]
+    Unfollow(String),
+    #[command(description = "List the cities you follow and the available ones")]
+    Cities,
+    #[command(description = "List the properties you're interested in")]
+    Shortlist,
+    #[command(description = "List the properties you applied for")]
+    Applied,
+    #[command(description = "List the properties you rejected")]
+    Rejected,
 }
 
-pub async fn run_bot(bot: Bot) -> anyhow::Result<()> {
-    let state = Arc::new(BotContext::new(bot).await?);
+pub async fn run_bot(bot: Bot, config: Config) -> anyhow::Result<()> {
+    let state = Arc::new(BotContext::new(bot, config).await?);
 
     let message_handling_task = tokio::spawn(state.clone().message_task());
-    let scraper_task = tokio::spawn(state.scraper_task());
-    let (message_handling_result, scraper_result) =
-        tokio::join!(message_handling_task, scraper_task);
+    let scraper_task = tokio::spawn(state.clone().scraper_task());
+    let digest_task = tokio::spawn(state.digest_task());
+    let (message_handling_result, scraper_result, digest_result) =
+        tokio::join!(message_handling_task, scraper_task, digest_task);
 
     if let Err(e) = message_handling_result {
         tracing::error!("Message handling task failed: {:?}", e);
@@ -43,27 +127,52 @@ pub async fn run_bot(bot: Bot) -> anyhow::Result<()> {
         tracing::error!("Scraper task failed: {:?}", e);
     }
 
+    if let Err(e) = digest_result {
+        tracing::error!("Digest task failed: {:?}", e);
+    }
+
     Ok(())
 }
 
-#[derive(Clone)]
 struct BotContext {
     persistence: Persistence,
     bot: Bot,
+    http: HttpClient,
+    config: Config,
+    /// Chats going through the onboarding wizard.
+    wizards: Mutex<HashMap<ChatId, Wizard>>,
 }
 
 impl BotContext {
-    async fn new(bot: Bot) -> anyhow::Result<Self> {
+    async fn new(bot: Bot, config: Config) -> anyhow::Result<Self> {
         Ok(Self {
-            persistence: Persistence::new().await?,
+            persistence: Persistence::new(&config.database_url).await?,
             bot,
+            http: HttpClient::new(&config.http)?,
+            config,
+            wizards: Mutex::new(HashMap::new()),
         })
     }
 
     async fn message_task(self: Arc<Self>) {
-        let handler = Update::filter_message()
-            .filter_command::<Command>()
-            .endpoint(handle_command);
+        let handler = dptree::entry()
+            .branch(
+                Update::filter_message()
+                    .branch(
+                        dptree::entry()
+                            .filter_command::<Command>()
+                            .endpoint(handle_command),
+                    )
+                    .branch(
+                        dptree::filter(|msg: Message| msg.document().is_some())
+                            .endpoint(handle_document),
+                    )
+                    .branch(
+                        dptree::filter(|msg: Message| msg.location().is_some())
+                            .endpoint(handle_location),
+                    ),
+            )
+            .branch(Update::filter_callback_query().endpoint(handle_callback));
 
         Dispatcher::builder(self.bot.clone(), handler)
             .dependencies(dptree::deps![self.clone()])
@@ -75,98 +184,536 @@ impl BotContext {
     }
 
     async fn scraper_task(self: Arc<Self>) {
+        // Shared by all websites, to limit how many are scraped at once
+        let limit = Arc::new(Semaphore::new(self.config.max_concurrent_scrapes));
+
+        let tasks: Vec<_> = self
+            .config
+            .websites()
+            .into_iter()
+            .filter(|website| self.config.is_enabled(website))
+            .map(|website| tokio::spawn(self.clone().website_task(website, limit.clone())))
+            .collect();
+
+        if tasks.is_empty() {
+            tracing::warn!("No scrapers enabled");
+            return;
+        }
+
+        for result in futures::future::join_all(tasks).await {
+            if let Err(e) = result {
+                tracing::error!("Website task failed: {:?}", e);
+            }
+        }
+    }
+
+    /// Send the digests of subscribers who don't want a message for every property.
+    async fn digest_task(self: Arc<Self>) {
+        let mut interval = tokio::time::interval(DIGEST_CHECK_INTERVAL);
         loop {
-            tracing::info!("Starting scrape");
+            interval.tick().await;
+            if let Err(e) = self.send_due_digests().await {
+                tracing::error!("Failed to send digests: {:?}", e);
+            }
+        }
+    }
 
-            self.scrape_website_infallible::<ParariusScraper>().await;
-            self.scrape_website_infallible::<HuurwoningenScraper>()
-                .await;
-            //self.scrape_website_infallible::<IkwilhurenScraper>().await;
-            self.scrape_website_infallible::<RotterdamWonenScraper>()
-                .await;
-            self.scrape_website_infallible::<VerraMakelaarsScraper>()
-                .await;
-            self.scrape_website_infallible::<VestedaScraper>().await;
+    async fn send_due_digests(&self) -> anyhow::Result<()> {
+        let now = chrono::Utc::now();
+        for subscriber in self.persistence.list_digest_subscribers().await? {
+            // Nothing is queued while paused, what was queued before is sent once they resume
+            if subscriber.state.is_paused(now.timestamp())
+                || !subscriber
+                    .state
+                    .delivery
+                    .is_due(subscriber.last_digest_at, now)
+            {
+                continue;
+            }
+
+            let properties = self.persistence.list_queued(subscriber.chat_id).await?;
+            let sent = if properties.is_empty() {
+                true
+            } else {
+                let text = digest::describe_digest(properties);
+                match self
+                    .bot
+                    .send_message(ChatId(subscriber.chat_id), text)
+                    .await
+                {
+                    Ok(_) => true,
+                    Err(error) => {
+                        tracing::error!("Failed to send digest: {:?}", error);
+                        false
+                    }
+                }
+            };
+            self.persistence
+                .finish_digest(subscriber.chat_id, now.timestamp(), sent)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Scrape a single website forever, on its own interval.
+    async fn website_task(self: Arc<Self>, website: Website, limit: Arc<Semaphore>) {
+        let scrapers: anyhow::Result<Vec<(String, Box<dyn WebsiteScraper>)>> = self
+            .config
+            .cities(&website)
+            .into_iter()
+            .map(|city| {
+                let search = self.config.search(city);
+                let scraper =
+                    website.scraper(self.http.clone(), &search, &self.config.definitions)?;
+                Ok((city.to_string(), scraper))
+            })
+            .collect();
+        let scrapers = match scrapers {
+            Ok(scrapers) => Arc::new(scrapers),
+            Err(e) => {
+                tracing::error!("Failed to create scraper for {website}: {e:?}");
+                return;
+            }
+        };
+        if scrapers.is_empty() {
+            tracing::warn!("None of the cities are on {website}, not scraping it");
+            return;
+        }
+
+        // Jitter the first run too, so not all websites are scraped right at startup
+        let mut delay = self.config.scrape_jitter();
+        loop {
+            tokio::time::sleep(delay).await;
+            delay = self.config.scrape_interval(&website) + self.config.scrape_jitter();
+
+            let permit = limit
+                .clone()
+                .acquire_owned()
+                .await
+                .expect("semaphore is never closed");
+
+            tracing::info!("Starting scrape of {website}");
+            let started_at = chrono::Utc::now().timestamp();
+            // Run the scrape in its own task, so a panic doesn't stop future scrapes
+            let run = tokio::spawn({
+                let context = self.clone();
+                let website = website.clone();
+                let scrapers = scrapers.clone();
+                async move {
+                    let _permit = permit;
+                    tokio::time::timeout(
+                        context.config.scrape_timeout(),
+                        context.scrape_cities(&website, &scrapers),
+                    )
+                    .await
+                }
+            });
+
+            let outcome = match run.await {
+                Ok(Ok(Ok(stats))) => Ok(stats),
+                Ok(Ok(Err(e))) => {
+                    tracing::error!("Scrape failed for {website}: {e:?}");
+                    Err(format!("{e:#}"))
+                }
+                Ok(Err(_)) => {
+                    tracing::error!("Scrape timed out for {website}");
+                    Err("timed out".to_string())
+                }
+                Err(e) => {
+                    tracing::error!("Scrape task failed for {website}: {e:?}");
+                    Err(e.to_string())
+                }
+            };
 
-            tracing::info!("Sleeping for 5 minutes");
-            tokio::time::sleep(tokio::time::Duration::from_secs(300)).await;
+            let run = ScrapeRun {
+                started_at,
+                finished_at: chrono::Utc::now().timestamp(),
+                outcome,
+            };
+            if let Err(e) = self.check_health(&website, &run).await {
+                tracing::error!("Health check failed for {website}: {e:?}");
+            }
         }
     }
 
-    async fn scrape_website_infallible<S: WebsiteScraper + Default>(&self) {
-        let scraper = S::default();
+    /// Record a scrape run, and tell the admin if the scraper broke or works again.
+    async fn check_health(&self, website: &Website, run: &ScrapeRun) -> anyhow::Result<()> {
+        let max_failures = self.config.health.max_consecutive_failures;
+        let assess = |history: &ScrapeHistory, now: i64| {
+            Health::assess(
+                history,
+                now,
+                max_failures as usize,
+                self.config.stale_after(website),
+            )
+        };
+
+        // The health as of the previous run comes from the stored runs too,
+        // so a restart doesn't alert about the same problem again
+        let history = self
+            .persistence
+            .scrape_history(website, max_failures)
+            .await?;
+        let previous = assess(&history, history.last_run_at.unwrap_or(run.started_at));
+
+        self.persistence.save_scrape_run(website, run).await?;
+        let history = self
+            .persistence
+            .scrape_history(website, max_failures)
+            .await?;
+        let health = assess(&history, run.finished_at);
+        if !health.changed_from(&previous) {
+            return Ok(());
+        }
 
-        if let Err(e) = self.scrape_once(scraper).await {
-            tracing::error!("Scrape failed for {}: {e:?}", std::any::type_name::<S>());
+        let text = health.describe(website, run.finished_at);
+        tracing::warn!("{text}");
+        if let Some(admin_chat_id) = self.config.admin_chat_id {
+            self.bot.send_message(ChatId(admin_chat_id), text).await?;
         }
+        Ok(())
     }
 
-    #[tracing::instrument(skip_all, fields(scraper=std::any::type_name::<T>()))]
-    async fn scrape_once<T: WebsiteScraper>(&self, scraper: T) -> anyhow::Result<()> {
+    /// Scrape every city of a website, so one failing city doesn't stop the others.
+    async fn scrape_cities(
+        &self,
+        website: &Website,
+        scrapers: &[(String, Box<dyn WebsiteScraper>)],
+    ) -> anyhow::Result<ScrapeStats> {
+        let mut stats = ScrapeStats::default();
+        let mut failed_cities = Vec::new();
+        for (city, scraper) in scrapers {
+            match self.scrape_once(website, city, scraper.as_ref()).await {
+                Ok(city_stats) => stats += city_stats,
+                Err(e) => {
+                    tracing::error!("Scrape failed for {website} in {city}: {e:?}");
+                    failed_cities.push(format!("{city}: {e:#}"));
+                }
+            }
+        }
+
+        anyhow::ensure!(failed_cities.is_empty(), failed_cities.join("\n"));
+        Ok(stats)
+    }
+
+    #[tracing::instrument(skip_all, fields(scraper=%website, city=%city))]
+    async fn scrape_once(
+        &self,
+        website: &Website,
+        city: &str,
+        scraper: &dyn WebsiteScraper,
+    ) -> anyhow::Result<ScrapeStats> {
+        let now = chrono::Utc::now().timestamp();
         let existing_properties = self.persistence.list_properties().await?;
 
-        let properties = scraper.list_properties().await?;
+        let is_known = |url: &str| existing_properties.contains_key(url);
+        let Listings {
+            mut properties,
+            errors,
+            ..
+        } = scraper
+            .list_new_properties(&is_known, self.config.max_pages(website))
+            .await?;
+        for error in errors.iter() {
+            tracing::warn!("Failed to parse listing: {:?}\n{}", error.error, error.html);
+        }
+        if !errors.is_empty() {
+            tracing::warn!(
+                "{} of {} listings couldn't be parsed",
+                errors.len(),
+                errors.len() + properties.len()
+            );
+            // If none of them can be parsed the scraper is broken, rather than a few listings
+            anyhow::ensure!(
+                !properties.is_empty(),
+                "none of the listings could be parsed: {:#}",
+                errors[0].error
+            );
+        }
 
-        let new_properties: Vec<_> = properties
-            .into_iter()
-            .filter(|property| !existing_properties.contains(&property.url))
+        let events: Vec<_> = properties
+            .iter()
+            .filter_map(|property| {
+                ListingEvent::detect(existing_properties.get(&property.url), property)
+            })
             .collect();
+        let changed_properties = events.len();
+        tracing::info!("Found {changed_properties} new or changed properties");
 
-        tracing::info!("Found {} new properties", new_properties.len());
+        if changed_properties > 0 {
+            self.notify_subscribers(
+                website,
+                city,
+                scraper,
+                &mut properties,
+                &existing_properties,
+                now,
+            )
+            .await?;
+        }
 
-        if new_properties.is_empty() {
-            return Ok(());
+        // Save all properties to DB, so we know when they were last seen
+        for property in properties.iter() {
+            self.persistence
+                .save_property(website, city, property, now)
+                .await?;
         }
 
-        let subscribers = self.persistence.list_subscribers().await?;
+        let delisted = self
+            .persistence
+            .mark_delisted(website, city, now, now)
+            .await?;
+        if delisted > 0 {
+            tracing::info!("{delisted} properties were delisted");
+        }
+
+        Ok(ScrapeStats {
+            listings: properties.len(),
+            parse_errors: errors.len(),
+            new_listings: events
+                .iter()
+                .filter(|event| **event == ListingEvent::New)
+                .count(),
+        })
+    }
+
+    /// Notify subscribers about new properties, price changes and relisted properties.
+    async fn notify_subscribers(
+        &self,
+        website: &Website,
+        city: &str,
+        scraper: &dyn WebsiteScraper,
+        properties: &mut [ScrapeResult],
+        existing_properties: &HashMap<String, KnownProperty>,
+        now: i64,
+    ) -> anyhow::Result<()> {
+        let mut subscribers = self.persistence.list_subscribers().await?;
+        subscribers
+            .retain(|subscriber| subscriber.follows(city) && !subscriber.state.is_paused(now));
         if subscribers.is_empty() {
             return Ok(());
         }
 
-        // Notify subscribers if there are relevant properties
-        for property in new_properties.iter() {
-            if property.price >= 1800 || property.area < 55 {
+        for property in properties.iter_mut() {
+            let Some(event) =
+                ListingEvent::detect(existing_properties.get(&property.url), property)
+            else {
                 continue;
-            }
+            };
 
-            // Sleep for a bit to avoid rate limiting
-            tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
-            let full_property = scraper.full(property.clone()).await?;
-
-            if !DESIRED_LOCATION.contains(&full_property.location) {
+            let interested_subscribers: Vec<_> = subscribers
+                .iter()
+                .filter(|subscriber| subscriber.filter.matches(property))
+                .collect();
+            if interested_subscribers.is_empty() {
                 continue;
             }
 
-            for subscriber in subscribers.iter() {
-                let chat_id = ChatId(*subscriber);
-                if let Err(error) = self
-                    .bot
-                    .send_message(chat_id, format!("New property: {}", property.url))
-                    .await
-                {
-                    tracing::error!("Failed to send subscriber notification: {:?}", error);
+            // One broken property page shouldn't keep the rest from being notified and saved
+            let full_property = match scraper.full(property.clone()).await {
+                Ok(full_property) => full_property,
+                Err(error) => {
+                    tracing::warn!("Failed to scrape property {}: {:?}", property.url, error);
+                    continue;
                 }
+            };
 
-                if let Err(error) = self
-                    .bot
-                    .send_location(
-                        chat_id,
-                        full_property.location.y(),
-                        full_property.location.x(),
+            // Keep the details from the property page so they're saved too
+            *property = ScrapeResult::Full(full_property.clone());
+            let property_id = self
+                .persistence
+                .save_property(website, city, property, now)
+                .await?;
+
+            // Known properties were checked for duplicates when they were new
+            let original_id = match existing_properties.get(&property.url) {
+                Some(known) => known.duplicate_of,
+                None => {
+                    let fingerprint = Fingerprint::new(
+                        &full_property.title,
+                        full_property.postcode.as_deref(),
+                        full_property.area,
+                        full_property.price,
+                        Some(full_property.location),
+                    );
+                    let original_id = self
+                        .persistence
+                        .find_duplicate(website, &fingerprint)
+                        .await?;
+                    if let Some(original_id) = original_id {
+                        tracing::info!(
+                            "{} is a duplicate of property {original_id}",
+                            full_property.url
+                        );
+                        self.persistence
+                            .set_duplicate_of(property_id, original_id)
+                            .await?;
+                    }
+                    original_id
+                }
+            };
+
+            // Hidden properties are never sent again, not even when listed on another website
+            let hiding_chats = self
+                .persistence
+                .chats_hiding(property_id, original_id)
+                .await?;
+
+            // The property page may have details the listing page didn't have,
+            // so check the filters again.
+            for subscriber in interested_subscribers.into_iter().filter(|subscriber| {
+                !hiding_chats.contains(&subscriber.chat_id)
+                    && subscriber.filter.matches(&full_property)
+                    && location::in_any_region(&subscriber.regions, &full_property.location)
+                    && location::within_reach(
+                        &subscriber.points_of_interest,
+                        &full_property.location,
                     )
+            }) {
+                let chat_id = ChatId(subscriber.chat_id);
+
+                if subscriber.state.delivery != Delivery::Instant {
+                    self.persistence
+                        .queue_notification(chat_id.0, property_id, original_id, now)
+                        .await?;
+                    continue;
+                }
+
+                if let Some(original_id) = original_id {
+                    let folded = self
+                        .fold_duplicate(chat_id, original_id, website, &full_property.url)
+                        .await;
+                    match folded {
+                        Ok(true) => continue,
+                        Ok(false) => {}
+                        Err(error) => {
+                            tracing::error!("Failed to fold duplicate notification: {:?}", error)
+                        }
+                    }
+                }
+
+                let text = notification::describe_property(
+                    &full_property,
+                    website,
+                    event,
+                    &subscriber.points_of_interest,
+                );
+                // Subscribers may have tracked a property whose price changed
+                let status = self
+                    .persistence
+                    .get_property_status(chat_id.0, property_id)
+                    .await?;
+                let keyboard = notification::buttons(property_id, &full_property.url, status);
+                match self
+                    .send_notification(chat_id, &full_property, &text, keyboard)
                     .await
                 {
-                    tracing::error!("Failed to send subscriber notification: {:?}", error);
+                    Ok((message, has_photo)) => {
+                        self.persistence
+                            .save_notification(
+                                chat_id.0,
+                                property_id,
+                                message.id.0,
+                                &text,
+                                has_photo,
+                            )
+                            .await?
+                    }
+                    Err(error) => {
+                        tracing::error!("Failed to send subscriber notification: {:?}", error)
+                    }
                 }
             }
         }
 
-        // Save new properties to DB
-        for property in new_properties.iter() {
-            self.persistence.save_property(&property.url).await?;
+        Ok(())
+    }
+
+    /// Send a notification with the photo of the property, if it has one.
+    /// Returns the message and whether it has a photo.
+    async fn send_notification(
+        &self,
+        chat_id: ChatId,
+        property: &FullScrapeResult,
+        text: &str,
+        keyboard: InlineKeyboardMarkup,
+    ) -> Result<(Message, bool), teloxide::RequestError> {
+        let image_url = property
+            .image_url
+            .as_deref()
+            .filter(|_| text.chars().count() <= notification::MAX_CAPTION_LENGTH)
+            .and_then(|image_url| image_url.parse().ok());
+        if let Some(image_url) = image_url {
+            let sent = self
+                .bot
+                .send_photo(chat_id, InputFile::url(image_url))
+                .caption(text)
+                .reply_markup(keyboard.clone())
+                .await;
+            match sent {
+                Ok(message) => return Ok((message, true)),
+                // Telegram can't download every photo, the notification is more important
+                Err(error) => tracing::warn!(
+                    "Failed to send photo of {}, sending the notification without: {:?}",
+                    property.url,
+                    error
+                ),
+            }
         }
 
-        Ok(())
+        let message = self
+            .bot
+            .send_message(chat_id, text)
+            .reply_markup(keyboard)
+            .await?;
+        Ok((message, false))
+    }
+
+    /// Add the URL of a duplicate to the notification the subscriber got for the original property.
+    /// Returns false if the subscriber wasn't notified about the original property,
+    /// or the duplicate was added before, so it's news of its own, e.g. a price change.
+    async fn fold_duplicate(
+        &self,
+        chat_id: ChatId,
+        original_id: i64,
+        website: &Website,
+        url: &str,
+    ) -> anyhow::Result<bool> {
+        let Some(notification) = self
+            .persistence
+            .find_notification(chat_id.0, original_id)
+            .await?
+        else {
+            return Ok(false);
+        };
+        if notification.text.contains(url) {
+            return Ok(false);
+        }
+
+        let text = format!("{}\nAlso listed on {website}: {url}", notification.text);
+        let message_id = MessageId(notification.message_id);
+        // Editing a message drops its buttons, unless they're sent again
+        let keyboard =
+            notification::buttons(original_id, &notification.property_url, notification.status);
+        if notification.has_photo {
+            if text.chars().count() > notification::MAX_CAPTION_LENGTH {
+                return Ok(false);
+            }
+            self.bot
+                .edit_message_caption(chat_id, message_id)
+                .caption(&text)
+                .reply_markup(keyboard)
+                .await?;
+        } else {
+            self.bot
+                .edit_message_text(chat_id, message_id, &text)
+                .reply_markup(keyboard)
+                .await?;
+        }
+        self.persistence
+            .update_notification_text(notification.id, &text)
+            .await?;
+        Ok(true)
     }
 
     async fn handle_message_inner(
@@ -175,36 +722,704 @@ impl BotContext {
         msg: Message,
         cmd: Command,
     ) -> Result<(), BotError> {
+        // Any other command abandons the questions of /start.
+        if !matches!(cmd, Command::Start) {
+            self.wizards.lock().unwrap().remove(&msg.chat.id);
+        }
+
         match cmd {
             Command::Start => {
                 bot.send_message(msg.chat.id, Command::descriptions().to_string())
+                    .await?;
+
+                let cities = self.config.all_cities();
+                let wizard = Wizard::new(cities.into_iter().map(String::from).collect());
+                self.wizards
+                    .lock()
+                    .unwrap()
+                    .insert(msg.chat.id, wizard.clone());
+                self.ask(&bot, msg.chat.id, &wizard, None).await?
+            }
+            Command::Cancel => {
+                bot.send_message(msg.chat.id, "Stopped, use /start to start over")
                     .await?
             }
             Command::Subscribe => {
-                self.persistence
-                    .add_subscriber(msg.chat.id.0)
+                let subscribed = self
+                    .persistence
+                    .add_subscriber(msg.chat.id.0, &self.config.default_filter())
                     .await
                     .map_err(|_| BotError::Internal("failed to subscribe"))?;
-                bot.send_message(msg.chat.id, "Subscribed!").await?
+                let text = if subscribed {
+                    "Subscribed!"
+                } else {
+                    "You're already subscribed, see /status"
+                };
+                bot.send_message(msg.chat.id, text).await?
+            }
+            Command::Unsubscribe => {
+                let removed = self
+                    .persistence
+                    .remove_subscriber(msg.chat.id.0)
+                    .await
+                    .map_err(|_| BotError::Internal("failed to unsubscribe"))?;
+                if !removed {
+                    return Err(BotError::Internal("you're not subscribed"));
+                }
+                bot.send_message(
+                    msg.chat.id,
+                    "Unsubscribed, you won't get any more properties",
+                )
+                .await?
+            }
+            Command::Pause(arg) => {
+                let (paused_until, text) = if arg.trim().is_empty() {
+                    (i64::MAX, "Paused until you /resume".to_string())
+                } else {
+                    let until = parse_pause_duration(&arg)
+                        .and_then(|duration| chrono::Utc::now().checked_add_signed(duration))
+                        .ok_or(BotError::Internal(
+                            "usage: /pause [duration, e.g. 12h, 3d or 2w]",
+                        ))?
+                        .timestamp();
+                    (until, format!("Paused until {}", format_time(until)))
+                };
+                let updated = self
+                    .persistence
+                    .set_paused_until(msg.chat.id.0, Some(paused_until))
+                    .await
+                    .map_err(|_| BotError::Internal("failed to pause"))?;
+                if !updated {
+                    return Err(BotError::Internal("you're not subscribed"));
+                }
+                bot.send_message(msg.chat.id, text).await?
+            }
+            Command::Resume => {
+                let updated = self
+                    .persistence
+                    .set_paused_until(msg.chat.id.0, None)
+                    .await
+                    .map_err(|_| BotError::Internal("failed to resume"))?;
+                if !updated {
+                    return Err(BotError::Internal("you're not subscribed"));
+                }
+                bot.send_message(msg.chat.id, "Resumed, you'll get new properties again")
+                    .await?
+            }
+            Command::Delivery(arg) => {
+                let delivery = Delivery::parse(&arg).ok_or(BotError::Internal(
+                    "usage: /delivery instant, /delivery hourly or /delivery daily 08:00",
+                ))?;
+                let now = chrono::Utc::now().timestamp();
+                let updated = self
+                    .persistence
+                    .set_delivery(msg.chat.id.0, delivery, now)
+                    .await
+                    .map_err(|_| BotError::Internal("failed to change delivery"))?;
+                if !updated {
+                    return Err(BotError::Internal("you're not subscribed"));
+                }
+                bot.send_message(msg.chat.id, format!("You'll get new properties {delivery}"))
+                    .await?
+            }
+            Command::Status => {
+                let text = self
+                    .describe_status(msg.chat.id.0)
+                    .await
+                    .map_err(|_| BotError::Internal("failed to get status"))?;
+                bot.send_message(msg.chat.id, text).await?
+            }
+            Command::SetMinPrice(arg) => {
+                let min_price = parse_limit(&arg)?;
+                self.update_filter(msg.chat.id, |filter| filter.min_price = min_price)
+                    .await?;
+                bot.send_message(
+                    msg.chat.id,
+                    describe_limit("Minimum price", min_price.map(|price| format!("€{price}"))),
+                )
+                .await?
+            }
+            Command::SetMaxPrice(arg) => {
+                let max_price = parse_limit(&arg)?;
+                self.update_filter(msg.chat.id, |filter| filter.max_price = max_price)
+                    .await?;
+                bot.send_message(
+                    msg.chat.id,
+                    describe_limit("Maximum price", max_price.map(|price| format!("€{price}"))),
+                )
+                .await?
+            }
+            Command::SetMinArea(arg) => {
+                let min_area = parse_limit(&arg)?;
+                self.update_filter(msg.chat.id, |filter| filter.min_area = min_area)
+                    .await?;
+                bot.send_message(
+                    msg.chat.id,
+                    describe_limit("Minimum area", min_area.map(|area| format!("{area} m²"))),
+                )
+                .await?
+            }
+            Command::SetMinRooms(arg) => {
+                let min_rooms = parse_limit(&arg)?;
+                self.update_filter(msg.chat.id, |filter| filter.min_rooms = min_rooms)
+                    .await?;
+                bot.send_message(
+                    msg.chat.id,
+                    describe_limit("Minimum rooms", min_rooms.map(|rooms| rooms.to_string())),
+                )
+                .await?
+            }
+            Command::SetInterior(arg) => {
+                let interior: Option<Interior> = parse_limit(&arg)
+                    .map_err(|_| BotError::Internal("expected furnished, upholstered or shell"))?;
+                self.update_filter(msg.chat.id, |filter| filter.interior = interior)
+                    .await?;
+                bot.send_message(
+                    msg.chat.id,
+                    describe_limit("Interior", interior.map(|interior| interior.to_string())),
+                )
+                .await?
+            }
+            Command::AddRegion(arg) => {
+                let location = replied_location(&msg)?;
+                let (radius, name) = parse_distance_and_name(&arg).ok_or(BotError::Internal(
+                    "usage: /addregion <radius in km> <name>",
+                ))?;
+
+                let region = Region::circle(name, location, radius);
+                self.save_region(msg.chat.id, &region).await?;
+                bot.send_message(msg.chat.id, format!("Region {} saved", region.name))
+                    .await?
+            }
+            Command::Regions => {
+                let names = self
+                    .persistence
+                    .list_region_names(msg.chat.id.0)
+                    .await
+                    .map_err(|_| BotError::Internal("failed to list regions"))?;
+                let text = if names.is_empty() {
+                    "You have no regions, so you get properties from everywhere".to_string()
+                } else {
+                    format!("Your regions:\n{}", names.join("\n"))
+                };
+                bot.send_message(msg.chat.id, text).await?
+            }
+            Command::RemoveRegion(name) => {
+                let removed = self
+                    .persistence
+                    .remove_region(msg.chat.id.0, name.trim())
+                    .await
+                    .map_err(|_| BotError::Internal("failed to remove region"))?;
+                if !removed {
+                    return Err(BotError::Internal("no such region"));
+                }
+                bot.send_message(msg.chat.id, "Region removed").await?
+            }
+            Command::AddPoi(arg) => {
+                let location = replied_location(&msg)?;
+                let (max_distance, name) = parse_distance_and_name(&arg).ok_or(
+                    BotError::Internal("usage: /addpoi <max distance in km> <name>"),
+                )?;
+
+                let point = PointOfInterest {
+                    name,
+                    location,
+                    max_distance,
+                };
+                self.persistence
+                    .save_point_of_interest(msg.chat.id.0, &point)
+                    .await
+                    .map_err(|_| {
+                        BotError::Internal("failed to save point of interest, did you /subscribe?")
+                    })?;
+                bot.send_message(
+                    msg.chat.id,
+                    format!("Point of interest {} saved", point.name),
+                )
+                .await?
+            }
+            Command::Pois => {
+                let points = self
+                    .persistence
+                    .list_points_of_interest(msg.chat.id.0)
+                    .await
+                    .map_err(|_| BotError::Internal("failed to list points of interest"))?;
+                let text = if points.is_empty() {
+                    "You have no points of interest".to_string()
+                } else {
+                    points
+                        .iter()
+                        .map(|point| {
+                            format!(
+                                "{}: within {:.1} km",
+                                point.name,
+                                point.max_distance / 1000.0
+                            )
+                        })
+                        .join("\n")
+                };
+                bot.send_message(msg.chat.id, text).await?
+            }
+            Command::RemovePoi(name) => {
+                let removed = self
+                    .persistence
+                    .remove_point_of_interest(msg.chat.id.0, name.trim())
+                    .await
+                    .map_err(|_| BotError::Internal("failed to remove point of interest"))?;
+                if !removed {
+                    return Err(BotError::Internal("no such point of interest"));
+                }
+                bot.send_message(msg.chat.id, "Point of interest removed")
+                    .await?
+            }
+            Command::Follow(city) => {
+                let city = normalize_city(&city);
+                let available = self.config.all_cities();
+                if !available.contains(&city.as_str()) {
+                    bot.send_message(
+                        msg.chat.id,
+                        format!("Unknown city, available cities: {}", available.join(", ")),
+                    )
+                    .await?;
+                    return Ok(());
+                }
+                self.persistence
+                    .follow_city(msg.chat.id.0, &city)
+                    .await
+                    .map_err(|_| {
+                        BotError::Internal("failed to follow city, did you /subscribe?")
+                    })?;
+                bot.send_message(msg.chat.id, format!("You now follow {city}"))
+                    .await?
+            }
+            Command::Unfollow(city) => {
+                let removed = self
+                    .persistence
+                    .unfollow_city(msg.chat.id.0, &normalize_city(&city))
+                    .await
+                    .map_err(|_| BotError::Internal("failed to unfollow city"))?;
+                if !removed {
+                    return Err(BotError::Internal("you don't follow that city"));
+                }
+                bot.send_message(msg.chat.id, "City unfollowed").await?
+            }
+            Command::Cities => {
+                let followed = self
+                    .persistence
+                    .list_followed_cities(msg.chat.id.0)
+                    .await
+                    .map_err(|_| BotError::Internal("failed to list cities"))?;
+                let followed = if followed.is_empty() {
+                    "You follow no cities, so you get properties from all of them".to_string()
+                } else {
+                    format!("You follow: {}", followed.join(", "))
+                };
+                let text = format!(
+                    "{followed}\nAvailable cities: {}",
+                    self.config.all_cities().join(", ")
+                );
+                bot.send_message(msg.chat.id, text).await?
+            }
+            Command::Shortlist => {
+                let text = self
+                    .describe_tracked(msg.chat.id.0, PropertyStatus::Interested)
+                    .await?;
+                bot.send_message(msg.chat.id, text).await?
+            }
+            Command::Applied => {
+                let text = self
+                    .describe_tracked(msg.chat.id.0, PropertyStatus::Applied)
+                    .await?;
+                bot.send_message(msg.chat.id, text).await?
+            }
+            Command::Rejected => {
+                let text = self
+                    .describe_tracked(msg.chat.id.0, PropertyStatus::Rejected)
+                    .await?;
+                bot.send_message(msg.chat.id, text).await?
             }
         };
 
         Ok(())
     }
 
-    async fn handle_message(&self, bot: Bot, msg: Message, cmd: Command) -> ResponseResult<()> {
-        match self
-            .handle_message_inner(bot.clone(), msg.clone(), cmd.clone())
+    /// List the properties a chat gave `status`, most recent first.
+    async fn describe_tracked(
+        &self,
+        chat_id: i64,
+        status: PropertyStatus,
+    ) -> Result<String, BotError> {
+        let properties = self
+            .persistence
+            .list_tracked_properties(chat_id, status)
+            .await
+            .map_err(|_| BotError::Internal("failed to list properties"))?;
+        if properties.is_empty() {
+            return Ok(format!(
+                "You have no {} properties, use the buttons under notifications to track them",
+                status.as_str()
+            ));
+        }
+
+        let mut text = properties
+            .iter()
+            .take(MAX_TRACKED_LISTED)
+            .map(|property| {
+                let mut line = property.title.clone();
+                if let Some(price) = property.price {
+                    line.push_str(&format!(", {}", describe_rent(price).to_lowercase()));
+                }
+                if let Some(area) = property.area {
+                    line.push_str(&format!(", {area} m²"));
+                }
+                if property.delisted {
+                    line.push_str(" (no longer listed)");
+                }
+                let since = format_time(property.updated_at);
+                format!("{line}\n{}\nSince {since}", property.url)
+            })
+            .join("\n\n");
+        if properties.len() > MAX_TRACKED_LISTED {
+            text.push_str(&format!(
+                "\n\nAnd {} older ones",
+                properties.len() - MAX_TRACKED_LISTED
+            ));
+        }
+        Ok(text)
+    }
+
+    /// The subscription state and all settings of a chat.
+    async fn describe_status(&self, chat_id: i64) -> anyhow::Result<String> {
+        let Some(state) = self.persistence.get_subscriber_state(chat_id).await? else {
+            return Ok("You're not subscribed, use /subscribe to get new properties".to_string());
+        };
+
+        let now = chrono::Utc::now().timestamp();
+        let mut lines = vec![match state.paused_until {
+            Some(i64::MAX) => "You're subscribed, but paused until you /resume".to_string(),
+            Some(until) if state.is_paused(now) => {
+                format!("You're subscribed, but paused until {}", format_time(until))
+            }
+            _ => "You're subscribed".to_string(),
+        }];
+        lines.push(format!("You get new properties {}", state.delivery));
+
+        let filter = self.persistence.get_filter(chat_id).await?;
+        let price = |price: Option<usize>| price.map(|price| format!("€{price}"));
+        lines.push(describe_setting("Minimum price", price(filter.min_price)));
+        lines.push(describe_setting("Maximum price", price(filter.max_price)));
+        lines.push(describe_setting(
+            "Minimum area",
+            filter.min_area.map(|area| format!("{area} m²")),
+        ));
+        lines.push(describe_setting(
+            "Minimum rooms",
+            filter.min_rooms.map(|rooms| rooms.to_string()),
+        ));
+        lines.push(describe_setting(
+            "Interior",
+            filter.interior.map(|interior| interior.to_string()),
+        ));
+
+        let cities = self.persistence.list_followed_cities(chat_id).await?;
+        lines.push(format!(
+            "Cities: {}",
+            if cities.is_empty() {
+                "all".to_string()
+            } else {
+                cities.join(", ")
+            }
+        ));
+
+        let regions = self.persistence.list_region_names(chat_id).await?;
+        lines.push(format!(
+            "Regions: {}",
+            if regions.is_empty() {
+                "everywhere".to_string()
+            } else {
+                regions.join(", ")
+            }
+        ));
+
+        let points = self.persistence.list_points_of_interest(chat_id).await?;
+        if !points.is_empty() {
+            let points = points
+                .iter()
+                .map(|point| format!("{} ({:.1} km)", point.name, point.max_distance / 1000.0))
+                .join(", ");
+            lines.push(format!("Points of interest: {points}"));
+        }
+
+        Ok(lines.join("\n"))
+    }
+
+    async fn handle_document_inner(&self, bot: Bot, msg: Message) -> Result<(), BotError> {
+        let Some(document) = msg.document() else {
+            return Ok(());
+        };
+
+        if document.file.size > MAX_REGION_FILE_SIZE {
+            return Err(BotError::Internal("file too large"));
+        }
+
+        let name = msg
+            .caption()
+            .or_else(|| {
+                document
+                    .file_name
+                    .as_deref()
+                    .and_then(|file_name| Path::new(file_name).file_stem()?.to_str())
+            })
+            .ok_or(BotError::Internal("add the region name as caption"))?;
+
+        let file = bot.get_file(document.file.id.clone()).await?;
+        let mut raw = Vec::new();
+        bot.download_file(&file.path, &mut raw)
             .await
+            .map_err(|_| BotError::Internal("failed to download file"))?;
+        let raw = String::from_utf8(raw).map_err(|_| BotError::Internal("invalid GeoJSON"))?;
+
+        let region = Region::from_geojson(name.trim().to_string(), &raw)
+            .map_err(|_| BotError::Internal("invalid GeoJSON, expected a (multi)polygon"))?;
+        self.save_region(msg.chat.id, &region).await?;
+        bot.send_message(msg.chat.id, format!("Region {} saved", region.name))
+            .await?;
+
+        Ok(())
+    }
+
+    async fn save_region(&self, chat_id: ChatId, region: &Region) -> Result<(), BotError> {
+        self.persistence
+            .save_region(chat_id.0, region)
+            .await
+            .map_err(|_| BotError::Internal("failed to save region, did you /subscribe?"))
+    }
+
+    async fn update_filter(
+        &self,
+        chat_id: ChatId,
+        update: impl FnOnce(&mut Filter),
+    ) -> Result<(), BotError> {
+        let mut filter = self
+            .persistence
+            .get_filter(chat_id.0)
+            .await
+            .map_err(|_| BotError::Internal("failed to get filter"))?;
+        update(&mut filter);
+        self.persistence
+            .save_filter(chat_id.0, &filter)
+            .await
+            .map_err(|_| BotError::Internal("failed to save filter, did you /subscribe?"))
+    }
+
+    /// Ask the current question of the wizard, by editing the message with the previous
+    /// question if there is one.
+    async fn ask(
+        &self,
+        bot: &Bot,
+        chat_id: ChatId,
+        wizard: &Wizard,
+        previous: Option<MessageId>,
+    ) -> Result<Message, BotError> {
+        let (text, keyboard) = wizard.question();
+        let message = match (previous, keyboard) {
+            (Some(message_id), Some(keyboard)) => {
+                bot.edit_message_text(chat_id, message_id, text)
+                    .reply_markup(keyboard)
+                    .await?
+            }
+            // Without a keyboard the buttons of the previous question are removed
+            (Some(message_id), None) => bot.edit_message_text(chat_id, message_id, text).await?,
+            (None, Some(keyboard)) => {
+                bot.send_message(chat_id, text)
+                    .reply_markup(keyboard)
+                    .await?
+            }
+            (None, None) => bot.send_message(chat_id, text).await?,
+        };
+        Ok(message)
+    }
+
+    /// Save the answers of a finished wizard, subscribing the chat if it wasn't yet.
+    /// A region from the wizard replaces all other regions of the chat.
+    async fn finish_wizard(&self, chat_id: i64, wizard: &Wizard) -> anyhow::Result<()> {
+        self.persistence
+            .add_subscriber(chat_id, &self.config.default_filter())
+            .await?;
+
+        let mut filter = self.persistence.get_filter(chat_id).await?;
+        wizard.apply(&mut filter);
+        self.persistence.save_filter(chat_id, &filter).await?;
+
+        if let Some(cities) = wizard.cities() {
+            self.persistence
+                .set_followed_cities(chat_id, &cities)
+                .await?;
+        }
+        if let Some(region) = wizard.region() {
+            for name in self.persistence.list_region_names(chat_id).await? {
+                if name != region.name {
+                    self.persistence.remove_region(chat_id, &name).await?;
+                }
+            }
+            self.persistence.save_region(chat_id, region).await?;
+        }
+        Ok(())
+    }
+
+    async fn handle_callback_inner(
+        &self,
+        bot: &Bot,
+        message: &Message,
+        data: &str,
+    ) -> Result<(), BotError> {
+        let chat_id = message.chat.id;
+        if let Some(answer) = data.strip_prefix(wizard::CALLBACK_PREFIX) {
+            let wizard = {
+                let mut wizards = self.wizards.lock().unwrap();
+                let wizard = wizards.get_mut(&chat_id).ok_or(BotError::Internal(
+                    "this question expired, use /start to start over",
+                ))?;
+                if !wizard.answer(answer) {
+                    return Ok(());
+                }
+                wizard.clone()
+            };
+
+            if wizard.is_done() {
+                self.wizards.lock().unwrap().remove(&chat_id);
+                self.finish_wizard(chat_id.0, &wizard)
+                    .await
+                    .map_err(|_| BotError::Internal("failed to save your settings"))?;
+            }
+            self.ask(bot, chat_id, &wizard, Some(message.id)).await?;
+        } else if let Some((action, property_id)) = data
+            .strip_prefix(notification::CALLBACK_PREFIX)
+            .and_then(PropertyAction::parse)
         {
-            Ok(()) => Ok(()),
-            Err(BotError::Telegram(e)) => Err(e),
-            Err(BotError::Internal(err)) => {
-                bot.send_message(msg.chat.id, format!("Error: {:?}", err))
+            self.handle_property_action(bot, message, action, property_id)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Handle the buttons under a notification about a property.
+    async fn handle_property_action(
+        &self,
+        bot: &Bot,
+        message: &Message,
+        action: PropertyAction,
+        property_id: i64,
+    ) -> Result<(), BotError> {
+        let chat_id = message.chat.id;
+        match action {
+            PropertyAction::ShowOnMap => {
+                let location = self
+                    .persistence
+                    .property_location(property_id)
+                    .await
+                    .map_err(|_| BotError::Internal("failed to get the property location"))?
+                    .ok_or(BotError::Internal(
+                        "the location of this property is unknown",
+                    ))?;
+                bot.send_location(chat_id, location.y(), location.x())
+                    .reply_parameters(ReplyParameters::new(message.id))
+                    .await?;
+            }
+            PropertyAction::SetStatus(status) => {
+                let current = self
+                    .persistence
+                    .get_property_status(chat_id.0, property_id)
+                    .await
+                    .map_err(|_| BotError::Internal("failed to get the property status"))?;
+                // Pressing the button of the current status again clears it
+                let status = (current != Some(status)).then_some(status);
+                self.persistence
+                    .set_property_status(chat_id.0, property_id, status)
+                    .await
+                    .map_err(|_| {
+                        BotError::Internal("failed to save status, did you /subscribe?")
+                    })?;
+
+                // Telegram only lets bots delete recent messages,
+                // older ones show they're hidden instead.
+                if status == Some(PropertyStatus::Hidden)
+                    && bot.delete_message(chat_id, message.id).await.is_ok()
+                {
+                    return Ok(());
+                }
+                let keyboard = message
+                    .reply_markup()
+                    .cloned()
+                    .ok_or(BotError::Internal("this notification has no buttons"))?;
+                bot.edit_message_reply_markup(chat_id, message.id)
+                    .reply_markup(notification::with_status(keyboard, status))
                     .await?;
-                Ok(())
             }
         }
+        Ok(())
+    }
+
+    async fn handle_callback(&self, bot: Bot, query: CallbackQuery) -> ResponseResult<()> {
+        bot.answer_callback_query(query.id.clone()).await?;
+        let Some(message) = query.regular_message() else {
+            return Ok(());
+        };
+        let data = query.data.as_deref().unwrap_or_default();
+        let result = self.handle_callback_inner(&bot, message, data).await;
+        report_error(&bot, message.chat.id, result).await
+    }
+
+    /// Locations are only handled on their own when the wizard asks for one,
+    /// otherwise they're used by replying to them with a command.
+    async fn handle_location(&self, bot: Bot, msg: Message) -> ResponseResult<()> {
+        let Some(location) = msg.location() else {
+            return Ok(());
+        };
+        let location = geo::Point::new(location.longitude, location.latitude);
+
+        let wizard = {
+            let mut wizards = self.wizards.lock().unwrap();
+            let Some(wizard) = wizards.get_mut(&msg.chat.id) else {
+                return Ok(());
+            };
+            if !wizard.located(location) {
+                return Ok(());
+            }
+            wizard.clone()
+        };
+        let result = self.ask(&bot, msg.chat.id, &wizard, None).await.map(|_| ());
+        report_error(&bot, msg.chat.id, result).await
+    }
+
+    async fn handle_message(&self, bot: Bot, msg: Message, cmd: Command) -> ResponseResult<()> {
+        let result = self
+            .handle_message_inner(bot.clone(), msg.clone(), cmd.clone())
+            .await;
+        report_error(&bot, msg.chat.id, result).await
+    }
+
+    async fn handle_document(&self, bot: Bot, msg: Message) -> ResponseResult<()> {
+        let result = self.handle_document_inner(bot.clone(), msg.clone()).await;
+        report_error(&bot, msg.chat.id, result).await
+    }
+}
+
+/// Internal errors are reported back to the user, Telegram errors are passed on to the dispatcher.
+async fn report_error(
+    bot: &Bot,
+    chat_id: ChatId,
+    result: Result<(), BotError>,
+) -> ResponseResult<()> {
+    match result {
+        Ok(()) => Ok(()),
+        Err(BotError::Telegram(e)) => Err(e),
+        Err(BotError::Internal(err)) => {
+            bot.send_message(chat_id, format!("Error: {:?}", err))
+                .await?;
+            Ok(())
+        }
     }
 }
 
@@ -217,6 +1432,80 @@ async fn handle_command(
     state.handle_message(bot, msg, cmd).await
 }
 
+async fn handle_document(bot: Bot, msg: Message, state: Arc<BotContext>) -> ResponseResult<()> {
+    state.handle_document(bot, msg).await
+}
+
+async fn handle_location(bot: Bot, msg: Message, state: Arc<BotContext>) -> ResponseResult<()> {
+    state.handle_location(bot, msg).await
+}
+
+async fn handle_callback(
+    bot: Bot,
+    query: CallbackQuery,
+    state: Arc<BotContext>,
+) -> ResponseResult<()> {
+    state.handle_callback(bot, query).await
+}
+
+/// Parse the argument of a filter command. An empty argument clears the limit.
+fn parse_limit<T: FromStr>(arg: &str) -> Result<Option<T>, BotError> {
+    let arg = arg.trim();
+    if arg.is_empty() {
+        return Ok(None);
+    }
+
+    arg.parse()
+        .map(Some)
+        .map_err(|_| BotError::Internal("expected a whole number"))
+}
+
+fn replied_location(msg: &Message) -> Result<geo::Point, BotError> {
+    let location = msg
+        .reply_to_message()
+        .and_then(Message::location)
+        .ok_or(BotError::Internal("reply to a location with this command"))?;
+    Ok(geo::Point::new(location.longitude, location.latitude))
+}
+
+/// Parse "<distance in km> <name>", returning the distance in meters.
+fn parse_distance_and_name(arg: &str) -> Option<(f64, String)> {
+    let (distance, name) = arg.trim().split_once(char::is_whitespace)?;
+    let distance: f64 = distance.parse().ok()?;
+    Some((distance * 1000.0, name.trim().to_string()))
+}
+
+fn describe_limit(name: &str, limit: Option<String>) -> String {
+    match limit {
+        Some(limit) => format!("{name} set to {limit}"),
+        None => format!("{name} cleared"),
+    }
+}
+
+fn describe_setting(name: &str, value: Option<String>) -> String {
+    format!("{name}: {}", value.as_deref().unwrap_or("any"))
+}
+
+/// Parse a duration like "12h", "3d" or "2w".
+fn parse_pause_duration(arg: &str) -> Option<chrono::Duration> {
+    let arg = arg.trim();
+    let unit_start = arg.find(|c: char| !c.is_ascii_digit())?;
+    let (amount, unit) = arg.split_at(unit_start);
+    let amount: i64 = amount.parse().ok()?;
+    match unit.trim() {
+        "h" => chrono::Duration::try_hours(amount),
+        "d" => chrono::Duration::try_days(amount),
+        "w" => chrono::Duration::try_weeks(amount),
+        _ => None,
+    }
+}
+
+fn format_time(timestamp: i64) -> String {
+    chrono::DateTime::from_timestamp(timestamp, 0)
+        .map(|time| time.format("%Y-%m-%d %H:%M UTC").to_string())
+        .unwrap_or_default()
+}
+
 // Quick and dirty error handling for now
 enum BotError {
     Internal(&'static str),
@@ -228,3 +1517,8 @@ impl From<teloxide::RequestError> for BotError {
         Self::Telegram(err)
     }
 }
+
+/// Cities are written as in URLs, e.g. "Den Haag" is "den-haag".
+fn normalize_city(city: &str) -> String {
+    city.split_whitespace().join("-").to_lowercase()
+}
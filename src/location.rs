@@ -1,29 +1,207 @@
-use std::cell::LazyCell;
-
-use geo::Polygon;
-
-pub(super) const DESIRED_LOCATION: LazyCell<Polygon> = LazyCell::new(|| {
-    Polygon::new(
-        vec![
-            (4.4496346, 51.9359046),
-            (4.4674873, 51.9398733),
-            (4.484911, 51.9416723),
-            (4.4922066, 51.9420956),
-            (4.5062828, 51.9333644),
-            (4.520359, 51.9271722),
-            (4.5143509, 51.9156323),
-            (4.4977856, 51.9180146),
-            (4.4874001, 51.9115556),
-            (4.4802761, 51.9052544),
-            (4.4679165, 51.9012827),
-            (4.4629383, 51.9093847),
-            (4.4572735, 51.9088022),
-            (4.455986, 51.9121909),
-            (4.4551277, 51.9155793),
-            (4.4544411, 51.9225672),
-            (4.4496346, 51.9360633),
+use anyhow::Context;
+use geo::{
+    Contains, Destination, Distance, Geometry, GeometryCollection, Haversine, MultiPolygon, Point,
+    Polygon,
+};
+use geojson::GeoJson;
+
+/// Number of vertices used to approximate a circle around a point.
+const CIRCLE_VERTICES: usize = 64;
+
+/// A named area a subscriber is interested in.
+#[derive(Debug, Clone)]
+pub(super) struct Region {
+    pub(super) name: String,
+    area: MultiPolygon,
+}
+
+impl Region {
+    /// Parse a region from a GeoJSON document.
+    /// Any (multi)polygons in the document are merged into a single region,
+    /// other geometries (e.g. points or lines) are ignored.
+    pub(super) fn from_geojson(name: String, raw: &str) -> anyhow::Result<Self> {
+        let geojson: GeoJson = raw.parse().context("invalid GeoJSON")?;
+        let collection = GeometryCollection::try_from(&geojson).context("invalid GeoJSON")?;
+
+        let polygons: Vec<Polygon> = collection
+            .into_iter()
+            .flat_map(|geometry| match geometry {
+                Geometry::Polygon(polygon) => vec![polygon],
+                Geometry::MultiPolygon(multi_polygon) => multi_polygon.0,
+                _ => vec![],
+            })
+            .collect();
+
+        anyhow::ensure!(!polygons.is_empty(), "GeoJSON contains no polygons");
+
+        Ok(Self {
+            name,
+            area: MultiPolygon::new(polygons),
+        })
+    }
+
+    /// Approximate a circle of `radius` meters around `center`.
+    pub(super) fn circle(name: String, center: Point, radius: f64) -> Self {
+        let exterior: Vec<_> = (0..=CIRCLE_VERTICES)
+            .map(|i| {
+                let bearing = 360.0 * i as f64 / CIRCLE_VERTICES as f64;
+                Haversine::destination(center, bearing, radius)
+            })
+            .collect();
+
+        Self {
+            name,
+            area: MultiPolygon::new(vec![Polygon::new(exterior.into(), vec![])]),
+        }
+    }
+
+    pub(super) fn to_geojson(&self) -> String {
+        geojson::Geometry::new(geojson::Value::from(&self.area)).to_string()
+    }
+
+    pub(super) fn contains(&self, location: &Point) -> bool {
+        self.area.contains(location)
+    }
+}
+
+/// Subscribers without any regions are interested in every location.
+pub(super) fn in_any_region(regions: &[Region], location: &Point) -> bool {
+    regions.is_empty() || regions.iter().any(|region| region.contains(location))
+}
+
+/// A place a subscriber wants to live close to, e.g. their office.
+#[derive(Debug, Clone)]
+pub(super) struct PointOfInterest {
+    pub(super) name: String,
+    pub(super) location: Point,
+    /// In meters, as the crow flies.
+    pub(super) max_distance: f64,
+}
+
+impl PointOfInterest {
+    /// Distance in meters from this point of interest to `location`.
+    pub(super) fn distance_to(&self, location: &Point) -> f64 {
+        Haversine::distance(self.location, *location)
+    }
+}
+
+/// A location has to be close enough to *all* points of interest, e.g. both partners' offices.
+pub(super) fn within_reach(points_of_interest: &[PointOfInterest], location: &Point) -> bool {
+    points_of_interest
+        .iter()
+        .all(|point| point.distance_to(location) <= point.max_distance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two blocks of Rotterdam, the second with a courtyard cut out of it.
+    const BLOCKS: &str = r#"{
+        "type": "FeatureCollection",
+        "features": [
+            {
+                "type": "Feature",
+                "properties": {},
+                "geometry": {
+                    "type": "MultiPolygon",
+                    "coordinates": [
+                        [[[4.46, 51.91], [4.47, 51.91], [4.47, 51.92], [4.46, 51.92], [4.46, 51.91]]],
+                        [
+                            [[4.48, 51.91], [4.50, 51.91], [4.50, 51.93], [4.48, 51.93], [4.48, 51.91]],
+                            [[4.485, 51.915], [4.495, 51.915], [4.495, 51.925], [4.485, 51.925], [4.485, 51.915]]
+                        ]
+                    ]
+                }
+            },
+            {
+                "type": "Feature",
+                "properties": {},
+                "geometry": { "type": "Point", "coordinates": [4.40, 51.90] }
+            }
         ]
-        .into(),
-        vec![],
-    )
-});
+    }"#;
+
+    #[test]
+    fn parses_polygons_from_geojson() {
+        let region = Region::from_geojson("blocks".to_string(), BLOCKS).unwrap();
+
+        assert!(region.contains(&Point::new(4.465, 51.915)));
+        assert!(region.contains(&Point::new(4.482, 51.928)));
+        // Between the polygons of the MultiPolygon
+        assert!(!region.contains(&Point::new(4.475, 51.915)));
+        // In the courtyard
+        assert!(!region.contains(&Point::new(4.49, 51.92)));
+        // Points aren't areas
+        assert!(!region.contains(&Point::new(4.40, 51.90)));
+    }
+
+    #[test]
+    fn rejects_geojson_without_polygons() {
+        let point = r#"{ "type": "Point", "coordinates": [4.40, 51.90] }"#;
+        assert!(Region::from_geojson("point".to_string(), point).is_err());
+        assert!(Region::from_geojson("broken".to_string(), "{ \"type\": ").is_err());
+    }
+
+    #[test]
+    fn circle_contains_points_within_radius() {
+        let center = Point::new(4.4777, 51.9244);
+        let region = Region::circle("centrum".to_string(), center, 1000.0);
+
+        for bearing in [0.0, 45.0, 100.0, 270.0] {
+            assert!(region.contains(&Haversine::destination(center, bearing, 990.0)));
+            assert!(!region.contains(&Haversine::destination(center, bearing, 1010.0)));
+        }
+    }
+
+    #[test]
+    fn no_regions_is_everywhere() {
+        let location = Point::new(4.4777, 51.9244);
+        assert!(in_any_region(&[], &location));
+
+        let elsewhere = Region::circle("utrecht".to_string(), Point::new(5.1214, 52.0907), 1000.0);
+        let centrum = Region::circle("centrum".to_string(), location, 1000.0);
+        assert!(!in_any_region(std::slice::from_ref(&elsewhere), &location));
+        assert!(in_any_region(&[elsewhere, centrum], &location));
+    }
+
+    fn point_of_interest(name: &str, location: Point, max_distance: f64) -> PointOfInterest {
+        PointOfInterest {
+            name: name.to_string(),
+            location,
+            max_distance,
+        }
+    }
+
+    #[test]
+    fn measures_distance_as_the_crow_flies() {
+        let office = point_of_interest("office", Point::new(4.0, 52.0), 1000.0);
+        // A hundredth of a degree north is about 1112 m on any meridian
+        let distance = office.distance_to(&Point::new(4.0, 52.01));
+        assert!((distance - 1111.95).abs() < 0.01, "{distance}");
+    }
+
+    #[test]
+    fn within_reach_of_all_points_of_interest() {
+        let home = Point::new(4.4777, 51.9244);
+        let office = Point::new(4.4690, 51.9250);
+        let distance = point_of_interest("office", office, 0.0).distance_to(&home);
+
+        // The maximum distance is inclusive
+        assert!(within_reach(
+            &[point_of_interest("office", office, distance)],
+            &home
+        ));
+        assert!(!within_reach(
+            &[point_of_interest("office", office, distance - 0.01)],
+            &home
+        ));
+
+        let school = point_of_interest("school", Point::new(4.5125, 51.9283), 1000.0);
+        assert!(!within_reach(
+            &[point_of_interest("office", office, 1000.0), school],
+            &home
+        ));
+        assert!(within_reach(&[], &home));
+    }
+}
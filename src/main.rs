@@ -1,10 +1,26 @@
-use nlhousefinder::run_bot;
+use std::path::PathBuf;
+
+use clap::Parser;
+use nlhousefinder::{config::Config, run_bot};
 use teloxide::prelude::*;
 
+#[derive(Parser)]
+struct Args {
+    /// Path to a TOML configuration file. The defaults are used if not given.
+    #[arg(long)]
+    config: Option<PathBuf>,
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     tracing_subscriber::fmt::init();
 
+    let args = Args::parse();
+    let config = match args.config {
+        Some(path) => Config::load(&path)?,
+        None => Config::default(),
+    };
+
     let bot = Bot::from_env();
-    run_bot(bot).await
+    run_bot(bot, config).await
 }
@@ -0,0 +1,374 @@
+use reqwest::Url;
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardButtonKind, InlineKeyboardMarkup};
+
+use crate::{
+    location::PointOfInterest,
+    persistence::{KnownProperty, PropertyStatus},
+    scraping::{FullScrapeResult, PartialScrapeResult, Website, PRICE_ON_REQUEST},
+};
+
+/// Telegram doesn't accept longer photo captions, longer notifications are sent without photo.
+pub(super) const MAX_CAPTION_LENGTH: usize = 1024;
+
+/// Callback data of the buttons under notifications starts with this,
+/// followed by the action and the property ID.
+pub(super) const CALLBACK_PREFIX: &str = "property:";
+
+/// Why subscribers are notified about a property.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(super) enum ListingEvent {
+    New,
+    PriceChanged {
+        old_price: usize,
+    },
+    /// The property was delisted before, and is now listed again.
+    Relisted {
+        old_price: Option<usize>,
+    },
+}
+
+impl ListingEvent {
+    /// Compare a listed property to what we knew about it,
+    /// returning `None` if nothing changed.
+    pub(super) fn detect(
+        known: Option<&KnownProperty>,
+        property: &PartialScrapeResult,
+    ) -> Option<Self> {
+        let Some(known) = known else {
+            return Some(ListingEvent::New);
+        };
+
+        let old_price = known.price.filter(|price| *price != property.price);
+        if known.delisted {
+            Some(ListingEvent::Relisted { old_price })
+        } else {
+            old_price.map(|old_price| ListingEvent::PriceChanged { old_price })
+        }
+    }
+
+    fn old_price(&self) -> Option<usize> {
+        match self {
+            ListingEvent::New => None,
+            ListingEvent::PriceChanged { old_price } => Some(*old_price),
+            ListingEvent::Relisted { old_price } => *old_price,
+        }
+    }
+}
+
+/// Describe a property in a notification message, leaving out anything the website didn't list.
+/// The link to the property is in the buttons, see [`buttons`],
+/// unless it isn't a valid URL, then it's at the end of the text.
+pub(super) fn describe_property(
+    property: &FullScrapeResult,
+    website: &Website,
+    event: ListingEvent,
+    points_of_interest: &[PointOfInterest],
+) -> String {
+    let header = match event {
+        ListingEvent::New => "New property",
+        ListingEvent::PriceChanged { old_price }
+            if old_price == PRICE_ON_REQUEST || property.price == PRICE_ON_REQUEST =>
+        {
+            "Price change"
+        }
+        ListingEvent::PriceChanged { old_price } if old_price > property.price => "Price drop",
+        ListingEvent::PriceChanged { .. } => "Price increase",
+        ListingEvent::Relisted { .. } => "Listed again",
+    };
+    let mut lines = vec![format!("{header} on {website}")];
+
+    if !property.title.is_empty() {
+        lines.push(property.title.clone());
+    }
+
+    let mut size = describe_rent(property.price);
+    match event.old_price() {
+        Some(PRICE_ON_REQUEST) => size.push_str(" (was on request)"),
+        Some(old_price) => size.push_str(&format!(" (was €{old_price})")),
+        None => {}
+    }
+    size.push_str(&format!(", {} m²", property.area));
+    if let Some(price_per_m2) = price_per_m2(property.price, property.area) {
+        size.push_str(&format!(" (€{price_per_m2:.2} per m²)"));
+    }
+    if let Some(rooms) = property.rooms {
+        size.push_str(&format!(", {rooms} rooms"));
+    }
+    if let Some(bedrooms) = property.bedrooms {
+        size.push_str(&format!(" ({bedrooms} bedrooms)"));
+    }
+    lines.push(size);
+
+    if let Some(interior) = property.interior {
+        lines.push(format!("Interior: {interior}"));
+    }
+    if let Some(energy_label) = &property.energy_label {
+        lines.push(format!("Energy label: {energy_label}"));
+    }
+    if let Some(available_from) = property.available_from {
+        lines.push(format!("Available: {available_from}"));
+    }
+    if let Some(deposit) = property.deposit {
+        lines.push(format!("Deposit: €{deposit}"));
+    }
+    if let Some(postcode) = &property.postcode {
+        lines.push(format!("Postcode: {postcode}"));
+    }
+
+    for point in points_of_interest {
+        let distance = point.distance_to(&property.location);
+        lines.push(format!("{}: {:.1} km", point.name, distance / 1000.0));
+    }
+
+    if Url::parse(&property.url).is_err() {
+        lines.push(property.url.clone());
+    }
+
+    lines.join("\n")
+}
+
+/// The monthly rent, e.g. "€1500 per month", or "Price on request".
+pub(super) fn describe_rent(price: usize) -> String {
+    if price == PRICE_ON_REQUEST {
+        "Price on request".to_string()
+    } else {
+        format!("€{price} per month")
+    }
+}
+
+/// The monthly rent per square meter, unknown if the website didn't list the price or area.
+pub(super) fn price_per_m2(price: usize, area: u32) -> Option<f64> {
+    (price != PRICE_ON_REQUEST && area > 0).then(|| price as f64 / f64::from(area))
+}
+
+/// What the buttons under a notification do.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(super) enum PropertyAction {
+    ShowOnMap,
+    /// Set the status of the property, or clear it if it already has this status.
+    SetStatus(PropertyStatus),
+}
+
+impl PropertyAction {
+    fn callback_data(&self, property_id: i64) -> String {
+        let action = match self {
+            PropertyAction::ShowOnMap => "map",
+            PropertyAction::SetStatus(status) => status.as_str(),
+        };
+        format!("{CALLBACK_PREFIX}{action}:{property_id}")
+    }
+
+    /// Parse the callback data after `CALLBACK_PREFIX` into the action and the property ID.
+    pub(super) fn parse(data: &str) -> Option<(Self, i64)> {
+        let (action, property_id) = data.split_once(':')?;
+        let action = if action == "map" {
+            PropertyAction::ShowOnMap
+        } else {
+            PropertyAction::SetStatus(PropertyStatus::parse(action)?)
+        };
+        Some((action, property_id.parse().ok()?))
+    }
+}
+
+/// The buttons under a notification about a property, showing its current status.
+pub(super) fn buttons(
+    property_id: i64,
+    url: &str,
+    status: Option<PropertyStatus>,
+) -> InlineKeyboardMarkup {
+    let mut first_row = Vec::new();
+    // Websites don't always escape their links properly,
+    // the text of the notification has the link instead then
+    match Url::parse(url) {
+        Ok(url) => first_row.push(InlineKeyboardButton::url("Open listing", url)),
+        Err(e) => tracing::warn!("Not adding a button for invalid link {url}: {e}"),
+    }
+    first_row.push(InlineKeyboardButton::callback(
+        "Show on map",
+        PropertyAction::ShowOnMap.callback_data(property_id),
+    ));
+
+    let status_row = PropertyStatus::ALL
+        .into_iter()
+        .map(|button| {
+            InlineKeyboardButton::callback(
+                status_label(button, status),
+                PropertyAction::SetStatus(button).callback_data(property_id),
+            )
+        })
+        .collect();
+
+    InlineKeyboardMarkup::new([first_row, status_row])
+}
+
+/// Update the buttons of a notification to show the new status of its property.
+pub(super) fn with_status(
+    mut keyboard: InlineKeyboardMarkup,
+    status: Option<PropertyStatus>,
+) -> InlineKeyboardMarkup {
+    for button in keyboard.inline_keyboard.iter_mut().flatten() {
+        let InlineKeyboardButtonKind::CallbackData(data) = &button.kind else {
+            continue;
+        };
+        let action = data
+            .strip_prefix(CALLBACK_PREFIX)
+            .and_then(PropertyAction::parse);
+        if let Some((PropertyAction::SetStatus(button_status), _)) = action {
+            button.text = status_label(button_status, status);
+        }
+    }
+    keyboard
+}
+
+fn status_label(button: PropertyStatus, status: Option<PropertyStatus>) -> String {
+    let label = match button {
+        PropertyStatus::Interested => "Interested",
+        PropertyStatus::Applied => "Applied",
+        PropertyStatus::Rejected => "Rejected",
+        PropertyStatus::Hidden => "Hide",
+    };
+    if status == Some(button) {
+        format!("✓ {label}")
+    } else {
+        label.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn describes_price_per_m2() {
+        let property = FullScrapeResult {
+            partial: PartialScrapeResult {
+                title: "Kralingse Plaslaan 12".to_string(),
+                price: 1650,
+                url:
+                    "https://pararius.com/apartment-for-rent/rotterdam/1a2b3c4d/kralingse-plaslaan"
+                        .to_string(),
+                area: 72,
+                rooms: Some(3),
+                ..Default::default()
+            },
+            location: geo::Point::new(4.5125, 51.9283),
+        };
+
+        let text = describe_property(
+            &property,
+            &Website::Pararius,
+            ListingEvent::PriceChanged { old_price: 1700 },
+            &[],
+        );
+        assert_eq!(
+            text,
+            "Price drop on pararius\n\
+            Kralingse Plaslaan 12\n\
+            €1650 per month (was €1700), 72 m² (€22.92 per m²), 3 rooms"
+        );
+    }
+
+    #[test]
+    fn leaves_out_price_on_request() {
+        let property = FullScrapeResult {
+            partial: PartialScrapeResult {
+                title: "Witte de Withstraat 40-B".to_string(),
+                price: PRICE_ON_REQUEST,
+                url:
+                    "https://pararius.com/apartment-for-rent/rotterdam/5e6f7a8b/witte-de-withstraat"
+                        .to_string(),
+                area: 55,
+                ..Default::default()
+            },
+            location: geo::Point::new(4.4735, 51.9158),
+        };
+
+        let text = describe_property(
+            &property,
+            &Website::Pararius,
+            ListingEvent::PriceChanged { old_price: 1450 },
+            &[],
+        );
+        assert_eq!(
+            text,
+            "Price change on pararius\n\
+            Witte de Withstraat 40-B\n\
+            Price on request (was €1450), 55 m²"
+        );
+    }
+
+    #[test]
+    fn shows_invalid_links_in_text() {
+        let property = FullScrapeResult {
+            partial: PartialScrapeResult {
+                title: "Bergweg 55".to_string(),
+                price: 1200,
+                url: "/nl/aanbod/rotterdam/bergweg 55/".to_string(),
+                area: 48,
+                ..Default::default()
+            },
+            location: geo::Point::new(4.4781, 51.9333),
+        };
+
+        let text = describe_property(&property, &Website::Verra, ListingEvent::New, &[]);
+        assert_eq!(
+            text,
+            "New property on verra\n\
+            Bergweg 55\n\
+            €1200 per month, 48 m² (€25.00 per m²)\n\
+            /nl/aanbod/rotterdam/bergweg 55/"
+        );
+
+        let keyboard = buttons(42, &property.partial.url, None);
+        assert_eq!(button_texts(&keyboard)[0], "Show on map");
+    }
+
+    fn button_texts(keyboard: &InlineKeyboardMarkup) -> Vec<&str> {
+        keyboard
+            .inline_keyboard
+            .iter()
+            .flatten()
+            .map(|button| button.text.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn buttons_show_status() {
+        let keyboard = buttons(
+            42,
+            "https://www.verra.nl/nl/aanbod/rotterdam/bergweg-55/",
+            Some(PropertyStatus::Interested),
+        );
+        assert_eq!(
+            button_texts(&keyboard),
+            vec![
+                "Open listing",
+                "Show on map",
+                "✓ Interested",
+                "Applied",
+                "Rejected",
+                "Hide"
+            ]
+        );
+
+        let keyboard = with_status(keyboard, Some(PropertyStatus::Applied));
+        assert_eq!(
+            button_texts(&keyboard)[2..],
+            ["Interested", "✓ Applied", "Rejected", "Hide"]
+        );
+    }
+
+    #[test]
+    fn parses_actions() {
+        assert_eq!(
+            PropertyAction::parse("map:42"),
+            Some((PropertyAction::ShowOnMap, 42))
+        );
+        assert_eq!(
+            PropertyAction::parse("hidden:42"),
+            Some((PropertyAction::SetStatus(PropertyStatus::Hidden), 42))
+        );
+        assert_eq!(PropertyAction::parse("interested:abc"), None);
+        assert_eq!(PropertyAction::parse("viewed:42"), None);
+    }
+}
@@ -1,7 +1,127 @@
-use std::collections::HashSet;
+use std::collections::HashMap;
 
 use anyhow::Context;
-use sqlx::sqlite::SqlitePool;
+use sqlx::{sqlite::SqlitePool, SqliteExecutor};
+
+use crate::{
+    dedup::Fingerprint,
+    digest::Delivery,
+    filter::Filter,
+    health::{ScrapeHistory, ScrapeRun, ScrapeStats},
+    location::{PointOfInterest, Region},
+    scraping::{ScrapeResult, Website},
+};
+
+pub(super) struct Subscriber {
+    pub(super) chat_id: i64,
+    pub(super) filter: Filter,
+    pub(super) regions: Vec<Region>,
+    pub(super) points_of_interest: Vec<PointOfInterest>,
+    /// The cities the subscriber follows, all of them if empty.
+    pub(super) cities: Vec<String>,
+    pub(super) state: SubscriberState,
+}
+
+impl Subscriber {
+    pub(super) fn follows(&self, city: &str) -> bool {
+        self.cities.is_empty() || self.cities.iter().any(|followed| followed == city)
+    }
+}
+
+pub(super) struct SubscriberState {
+    /// Notifications aren't sent until this time.
+    pub(super) paused_until: Option<i64>,
+    pub(super) delivery: Delivery,
+}
+
+impl SubscriberState {
+    pub(super) fn is_paused(&self, now: i64) -> bool {
+        self.paused_until.is_some_and(|until| until > now)
+    }
+}
+
+/// What we knew about a property before the current scrape.
+pub(super) struct KnownProperty {
+    /// The last seen price, unknown for properties saved before prices were recorded.
+    pub(super) price: Option<usize>,
+    pub(super) delisted: bool,
+    /// The property this is a duplicate of, found when it was new.
+    pub(super) duplicate_of: Option<i64>,
+}
+
+/// Scrape runs older than this are deleted, they're only kept to judge the health of scrapers.
+const SCRAPE_RUN_RETENTION_SECS: i64 = 30 * 24 * 60 * 60;
+
+/// A notification message sent to a subscriber.
+pub(super) struct Notification {
+    pub(super) id: i64,
+    pub(super) message_id: i32,
+    pub(super) text: String,
+    /// The text is the caption of a photo.
+    pub(super) has_photo: bool,
+    pub(super) property_url: String,
+    pub(super) status: Option<PropertyStatus>,
+}
+
+/// What a subscriber did with a property, to keep track of the ones they like.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(super) enum PropertyStatus {
+    Interested,
+    Applied,
+    Rejected,
+    /// Never notify about the property again.
+    Hidden,
+}
+
+impl PropertyStatus {
+    pub(super) const ALL: [PropertyStatus; 4] = [
+        PropertyStatus::Interested,
+        PropertyStatus::Applied,
+        PropertyStatus::Rejected,
+        PropertyStatus::Hidden,
+    ];
+
+    pub(super) fn as_str(&self) -> &'static str {
+        match self {
+            PropertyStatus::Interested => "interested",
+            PropertyStatus::Applied => "applied",
+            PropertyStatus::Rejected => "rejected",
+            PropertyStatus::Hidden => "hidden",
+        }
+    }
+
+    pub(super) fn parse(status: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|candidate| candidate.as_str() == status)
+    }
+}
+
+/// A subscriber who gets digests, or still has notifications queued for one.
+pub(super) struct DigestSubscriber {
+    pub(super) chat_id: i64,
+    pub(super) state: SubscriberState,
+    pub(super) last_digest_at: i64,
+}
+
+/// A property queued for the next digest of a subscriber.
+pub(super) struct QueuedProperty {
+    pub(super) title: String,
+    pub(super) url: String,
+    pub(super) price: usize,
+    pub(super) area: u32,
+    pub(super) rooms: Option<u32>,
+}
+
+/// A property a subscriber gave a status.
+pub(super) struct TrackedProperty {
+    pub(super) title: String,
+    pub(super) url: String,
+    pub(super) price: Option<usize>,
+    pub(super) area: Option<u32>,
+    pub(super) updated_at: i64,
+    pub(super) delisted: bool,
+}
 
 #[derive(Clone)]
 pub(super) struct Persistence {
@@ -9,8 +129,8 @@ pub(super) struct Persistence {
 }
 
 impl Persistence {
-    pub(super) async fn new() -> anyhow::Result<Self> {
-        let pool = SqlitePool::connect("sqlite://database.db")
+    pub(super) async fn new(database_url: &str) -> anyhow::Result<Self> {
+        let pool = SqlitePool::connect(database_url)
             .await
             .context("failed to open db")?;
         sqlx::migrate!()
@@ -20,35 +140,1216 @@ impl Persistence {
         Ok(Self { pool })
     }
 
-    pub(super) async fn save_property(&self, url: &str) -> anyhow::Result<()> {
-        sqlx::query!("INSERT INTO properties (url) VALUES (?)", url)
+    /// Insert a new property or update a known one, marking it as seen at `seen_at`.
+    /// Details we only know from the property page are kept if `property` doesn't have them.
+    /// Returns the ID of the property.
+    pub(super) async fn save_property(
+        &self,
+        site: &Website,
+        city: &str,
+        property: &ScrapeResult,
+        seen_at: i64,
+    ) -> anyhow::Result<i64> {
+        let site = site.to_string();
+        let price = property.price as i64;
+        let (latitude, longitude) = match property {
+            ScrapeResult::Full(full) => (Some(full.location.y()), Some(full.location.x())),
+            ScrapeResult::Partial(_) => (None, None),
+        };
+        let interior = property.interior.map(|interior| interior.as_str());
+        let available_from = property.available_from.map(|date| date.to_string());
+        let deposit = property.deposit.map(|deposit| deposit as i64);
+        let result = sqlx::query!(
+            r#"INSERT INTO properties (
+                url, site, city, title, price, area, latitude, longitude,
+                rooms, bedrooms, interior, energy_label, available_from, deposit, postcode,
+                image_url, first_seen_at, last_seen_at
+            )
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT (url) DO UPDATE SET
+                site = excluded.site,
+                city = excluded.city,
+                title = COALESCE(NULLIF(excluded.title, ''), title),
+                price = excluded.price,
+                area = excluded.area,
+                latitude = COALESCE(excluded.latitude, latitude),
+                longitude = COALESCE(excluded.longitude, longitude),
+                rooms = COALESCE(excluded.rooms, rooms),
+                bedrooms = COALESCE(excluded.bedrooms, bedrooms),
+                interior = COALESCE(excluded.interior, interior),
+                energy_label = COALESCE(excluded.energy_label, energy_label),
+                available_from = COALESCE(excluded.available_from, available_from),
+                deposit = COALESCE(excluded.deposit, deposit),
+                postcode = COALESCE(excluded.postcode, postcode),
+                image_url = COALESCE(excluded.image_url, image_url),
+                last_seen_at = excluded.last_seen_at,
+                delisted_at = NULL
+            RETURNING id"#,
+            property.url,
+            site,
+            city,
+            property.title,
+            price,
+            property.area,
+            latitude,
+            longitude,
+            property.rooms,
+            property.bedrooms,
+            interior,
+            property.energy_label,
+            available_from,
+            deposit,
+            property.postcode,
+            property.image_url,
+            seen_at,
+            seen_at,
+        )
+        .fetch_one(&self.pool)
+        .await
+        .context("failed to save property")?;
+        Ok(result.id)
+    }
+
+    /// Mark properties of `site` in `city` that weren't seen since `seen_since` as delisted,
+    /// returning how many there were.
+    ///
+    /// Most scrapers only see the newest properties, so a property that wasn't seen might just
+    /// have been pushed off the listing page. Only properties which are at least as new as the
+    /// oldest property that *was* seen are therefore considered delisted.
+    pub(super) async fn mark_delisted(
+        &self,
+        site: &Website,
+        city: &str,
+        seen_since: i64,
+        delisted_at: i64,
+    ) -> anyhow::Result<u64> {
+        let site = site.to_string();
+        let result = sqlx::query!(
+            r#"UPDATE properties SET delisted_at = ?
+            WHERE site = ?
+                AND city = ?
+                AND delisted_at IS NULL
+                AND last_seen_at < ?
+                AND first_seen_at >= (
+                    SELECT MIN(first_seen_at) FROM properties
+                    WHERE site = ? AND city = ? AND last_seen_at >= ?
+                )"#,
+            delisted_at,
+            site,
+            city,
+            seen_since,
+            site,
+            city,
+            seen_since,
+        )
+        .execute(&self.pool)
+        .await
+        .context("failed to mark delisted properties")?;
+        Ok(result.rows_affected())
+    }
+
+    /// Find an active property on another website which is the same as `fingerprint`,
+    /// returning its ID.
+    pub(super) async fn find_duplicate(
+        &self,
+        site: &Website,
+        fingerprint: &Fingerprint,
+    ) -> anyhow::Result<Option<i64>> {
+        let site = site.to_string();
+        let (min_area, max_area) = fingerprint.area_range();
+        let candidates = sqlx::query!(
+            r#"SELECT id, title, postcode, area, price, latitude, longitude
+            FROM properties
+            WHERE (site IS NULL OR site != ?)
+                AND delisted_at IS NULL
+                AND duplicate_of IS NULL
+                AND area BETWEEN ? AND ?
+            ORDER BY first_seen_at"#,
+            site,
+            min_area,
+            max_area,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .context("failed to list duplicate candidates")?;
+
+        Ok(candidates
+            .into_iter()
+            .find(|candidate| {
+                let location = candidate
+                    .latitude
+                    .zip(candidate.longitude)
+                    .map(|(latitude, longitude)| geo::Point::new(longitude, latitude));
+                let candidate = Fingerprint::new(
+                    &candidate.title,
+                    candidate.postcode.as_deref(),
+                    candidate.area.unwrap_or_default() as u32,
+                    candidate.price.unwrap_or_default() as usize,
+                    location,
+                );
+                fingerprint.is_duplicate_of(&candidate)
+            })
+            .map(|candidate| candidate.id))
+    }
+
+    pub(super) async fn set_duplicate_of(&self, id: i64, original_id: i64) -> anyhow::Result<()> {
+        sqlx::query!(
+            "UPDATE properties SET duplicate_of = ? WHERE id = ?",
+            original_id,
+            id
+        )
+        .execute(&self.pool)
+        .await
+        .context("failed to mark duplicate property")?;
+        Ok(())
+    }
+
+    pub(super) async fn save_notification(
+        &self,
+        chat_id: i64,
+        property_id: i64,
+        message_id: i32,
+        text: &str,
+        has_photo: bool,
+    ) -> anyhow::Result<()> {
+        let sent_at = chrono::Utc::now().timestamp();
+        sqlx::query!(
+            r#"INSERT INTO notifications (chat_id, property_id, message_id, text, has_photo, sent_at)
+            VALUES (?, ?, ?, ?, ?, ?)"#,
+            chat_id,
+            property_id,
+            message_id,
+            text,
+            has_photo,
+            sent_at,
+        )
+        .execute(&self.pool)
+        .await
+        .context("failed to save notification")?;
+        Ok(())
+    }
+
+    pub(super) async fn find_notification(
+        &self,
+        chat_id: i64,
+        property_id: i64,
+    ) -> anyhow::Result<Option<Notification>> {
+        let result = sqlx::query!(
+            r#"SELECT notifications.id as "id!", message_id, text, has_photo, url, status as "status?"
+            FROM notifications
+            JOIN properties ON properties.id = notifications.property_id
+            LEFT JOIN property_statuses USING (chat_id, property_id)
+            WHERE chat_id = ? AND property_id = ?
+            ORDER BY sent_at DESC"#,
+            chat_id,
+            property_id,
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .context("failed to find notification")?;
+        Ok(result.map(|row| Notification {
+            id: row.id,
+            message_id: row.message_id as i32,
+            text: row.text,
+            has_photo: row.has_photo,
+            property_url: row.url,
+            status: row.status.as_deref().and_then(PropertyStatus::parse),
+        }))
+    }
+
+    /// The location of a property, unknown if we never scraped its property page.
+    pub(super) async fn property_location(&self, id: i64) -> anyhow::Result<Option<geo::Point>> {
+        let result = sqlx::query!(
+            "SELECT latitude, longitude FROM properties WHERE id = ?",
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .context("failed to get property location")?;
+        Ok(result.and_then(|row| {
+            let (latitude, longitude) = row.latitude.zip(row.longitude)?;
+            Some(geo::Point::new(longitude, latitude))
+        }))
+    }
+
+    pub(super) async fn update_notification_text(&self, id: i64, text: &str) -> anyhow::Result<()> {
+        sqlx::query!("UPDATE notifications SET text = ? WHERE id = ?", text, id)
             .execute(&self.pool)
             .await
-            .context("failed to save property")?;
+            .context("failed to update notification")?;
         Ok(())
     }
 
-    pub(super) async fn list_properties(&self) -> anyhow::Result<HashSet<String>> {
-        let result = sqlx::query!("SELECT url FROM properties")
+    /// Record a scrape of `site`, forgetting runs older than the retention period.
+    pub(super) async fn save_scrape_run(
+        &self,
+        site: &Website,
+        run: &ScrapeRun,
+    ) -> anyhow::Result<()> {
+        let site = site.to_string();
+        let (error, listings, parse_errors, new_listings) = match &run.outcome {
+            Ok(stats) => (
+                None,
+                Some(stats.listings as i64),
+                Some(stats.parse_errors as i64),
+                Some(stats.new_listings as i64),
+            ),
+            Err(error) => (Some(error.as_str()), None, None, None),
+        };
+        sqlx::query!(
+            r#"INSERT INTO scrape_runs
+                (site, started_at, finished_at, error, listings, parse_errors, new_listings)
+            VALUES (?, ?, ?, ?, ?, ?, ?)"#,
+            site,
+            run.started_at,
+            run.finished_at,
+            error,
+            listings,
+            parse_errors,
+            new_listings,
+        )
+        .execute(&self.pool)
+        .await
+        .context("failed to save scrape run")?;
+
+        let retain_since = run.started_at - SCRAPE_RUN_RETENTION_SECS;
+        sqlx::query!(
+            "DELETE FROM scrape_runs WHERE site = ? AND started_at < ?",
+            site,
+            retain_since,
+        )
+        .execute(&self.pool)
+        .await
+        .context("failed to delete old scrape runs")?;
+        Ok(())
+    }
+
+    /// The history of `site`'s scrapes, with up to `limit` recent runs.
+    pub(super) async fn scrape_history(
+        &self,
+        site: &Website,
+        limit: u32,
+    ) -> anyhow::Result<ScrapeHistory> {
+        let site = site.to_string();
+        let recent = sqlx::query!(
+            r#"SELECT error, listings, parse_errors, new_listings FROM scrape_runs
+            WHERE site = ?
+            ORDER BY started_at DESC
+            LIMIT ?"#,
+            site,
+            limit,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .context("failed to list scrape runs")?;
+
+        let summary = sqlx::query!(
+            r#"SELECT
+                MIN(started_at) as "first_run_at?: i64",
+                MAX(finished_at) as "last_run_at?: i64",
+                MAX(CASE WHEN new_listings > 0 THEN started_at END) as "last_new_listing_at?: i64"
+            FROM scrape_runs WHERE site = ?"#,
+            site,
+        )
+        .fetch_one(&self.pool)
+        .await
+        .context("failed to summarize scrape runs")?;
+
+        Ok(ScrapeHistory {
+            recent: recent
+                .into_iter()
+                .map(|row| match row.error {
+                    Some(error) => Err(error),
+                    None => Ok(ScrapeStats {
+                        listings: row.listings.unwrap_or_default() as usize,
+                        parse_errors: row.parse_errors.unwrap_or_default() as usize,
+                        new_listings: row.new_listings.unwrap_or_default() as usize,
+                    }),
+                })
+                .collect(),
+            first_run_at: summary.first_run_at,
+            last_run_at: summary.last_run_at,
+            last_new_listing_at: summary.last_new_listing_at,
+        })
+    }
+
+    /// List all known properties by URL.
+    pub(super) async fn list_properties(&self) -> anyhow::Result<HashMap<String, KnownProperty>> {
+        let result = sqlx::query!("SELECT url, price, delisted_at, duplicate_of FROM properties")
             .fetch_all(&self.pool)
             .await
             .context("failed to list properties")?;
-        Ok(result.iter().map(|row| row.url.clone()).collect())
+        Ok(result
+            .into_iter()
+            .map(|row| {
+                let property = KnownProperty {
+                    price: row.price.map(|price| price as usize),
+                    delisted: row.delisted_at.is_some(),
+                    duplicate_of: row.duplicate_of,
+                };
+                (row.url, property)
+            })
+            .collect())
+    }
+
+    /// Add a subscriber, starting out with the given filter.
+    /// Returns false if they were already subscribed, keeping their current filter.
+    pub(super) async fn add_subscriber(
+        &self,
+        chat_id: i64,
+        filter: &Filter,
+    ) -> anyhow::Result<bool> {
+        let mut transaction = self.pool.begin().await?;
+        let result = sqlx::query!(
+            "INSERT OR IGNORE INTO subscribers (chat_id) VALUES (?)",
+            chat_id
+        )
+        .execute(&mut *transaction)
+        .await
+        .context("failed to add subscriber")?;
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+        Self::save_filter_with(&mut *transaction, chat_id, filter).await?;
+        transaction.commit().await?;
+        Ok(true)
     }
 
-    pub(super) async fn add_subscriber(&self, chat_id: i64) -> anyhow::Result<()> {
-        sqlx::query!("INSERT INTO subscribers (chat_id) VALUES (?)", chat_id)
+    /// Remove a subscriber with all their settings.
+    /// Returns false if they weren't subscribed.
+    pub(super) async fn remove_subscriber(&self, chat_id: i64) -> anyhow::Result<bool> {
+        let result = sqlx::query!("DELETE FROM subscribers WHERE chat_id = ?", chat_id)
             .execute(&self.pool)
             .await
-            .context("failed to add subscriber")?;
-        Ok(())
+            .context("failed to remove subscriber")?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Pause notifications until `paused_until`, or resume them if it's `None`.
+    /// Returns false if the chat isn't subscribed.
+    pub(super) async fn set_paused_until(
+        &self,
+        chat_id: i64,
+        paused_until: Option<i64>,
+    ) -> anyhow::Result<bool> {
+        let result = sqlx::query!(
+            "UPDATE subscribers SET paused_until = ? WHERE chat_id = ?",
+            paused_until,
+            chat_id,
+        )
+        .execute(&self.pool)
+        .await
+        .context("failed to update subscriber")?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Change how a subscriber gets notified, starting the digest schedule at `now`.
+    /// Returns false if the chat isn't subscribed.
+    pub(super) async fn set_delivery(
+        &self,
+        chat_id: i64,
+        delivery: Delivery,
+        now: i64,
+    ) -> anyhow::Result<bool> {
+        let mode = delivery.mode();
+        let time = delivery.time();
+        let result = sqlx::query!(
+            "UPDATE subscribers SET delivery = ?, digest_time = ?, last_digest_at = ? WHERE chat_id = ?",
+            mode,
+            time,
+            now,
+            chat_id,
+        )
+        .execute(&self.pool)
+        .await
+        .context("failed to update subscriber")?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// `None` if the chat isn't subscribed.
+    pub(super) async fn get_subscriber_state(
+        &self,
+        chat_id: i64,
+    ) -> anyhow::Result<Option<SubscriberState>> {
+        let row = sqlx::query!(
+            "SELECT paused_until, delivery, digest_time FROM subscribers WHERE chat_id = ?",
+            chat_id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .context("failed to get subscriber")?;
+        Ok(row.map(|row| SubscriberState {
+            paused_until: row.paused_until,
+            delivery: stored_delivery(&row.delivery, row.digest_time.as_deref()),
+        }))
     }
 
-    pub(super) async fn list_subscribers(&self) -> anyhow::Result<Vec<i64>> {
-        let result = sqlx::query!("SELECT chat_id FROM subscribers")
+    pub(super) async fn list_subscribers(&self) -> anyhow::Result<Vec<Subscriber>> {
+        let result = sqlx::query!(
+            r#"SELECT
+                subscribers.chat_id,
+                paused_until,
+                delivery,
+                digest_time,
+                min_price,
+                max_price,
+                min_area,
+                min_rooms,
+                interior
+            FROM subscribers
+            LEFT JOIN subscriber_filters USING (chat_id)"#
+        )
+        .fetch_all(&self.pool)
+        .await
+        .context("failed to list subscribers")?;
+
+        let mut regions: HashMap<i64, Vec<Region>> = HashMap::new();
+        for row in sqlx::query!("SELECT chat_id, name, geometry FROM regions")
+            .fetch_all(&self.pool)
+            .await
+            .context("failed to list regions")?
+        {
+            let region = Region::from_geojson(row.name, &row.geometry)
+                .with_context(|| format!("invalid region stored for {}", row.chat_id))?;
+            regions.entry(row.chat_id).or_default().push(region);
+        }
+
+        let mut points_of_interest: HashMap<i64, Vec<PointOfInterest>> = HashMap::new();
+        for row in sqlx::query!(
+            "SELECT chat_id, name, latitude, longitude, max_distance FROM points_of_interest"
+        )
+        .fetch_all(&self.pool)
+        .await
+        .context("failed to list points of interest")?
+        {
+            points_of_interest
+                .entry(row.chat_id)
+                .or_default()
+                .push(PointOfInterest {
+                    name: row.name,
+                    location: geo::Point::new(row.longitude, row.latitude),
+                    max_distance: row.max_distance,
+                });
+        }
+
+        let mut cities: HashMap<i64, Vec<String>> = HashMap::new();
+        for row in sqlx::query!("SELECT chat_id, city FROM subscriber_cities ORDER BY city")
             .fetch_all(&self.pool)
             .await
-            .context("failed to list subscribers")?;
-        Ok(result.iter().map(|row| row.chat_id).collect())
+            .context("failed to list subscriber cities")?
+        {
+            cities.entry(row.chat_id).or_default().push(row.city);
+        }
+
+        Ok(result
+            .into_iter()
+            .map(|row| Subscriber {
+                chat_id: row.chat_id,
+                filter: Filter {
+                    min_price: row.min_price.map(|price| price as usize),
+                    max_price: row.max_price.map(|price| price as usize),
+                    min_area: row.min_area.map(|area| area as u32),
+                    min_rooms: row.min_rooms.map(|rooms| rooms as u32),
+                    interior: row.interior.and_then(|interior| interior.parse().ok()),
+                },
+                regions: regions.remove(&row.chat_id).unwrap_or_default(),
+                points_of_interest: points_of_interest.remove(&row.chat_id).unwrap_or_default(),
+                cities: cities.remove(&row.chat_id).unwrap_or_default(),
+                state: SubscriberState {
+                    paused_until: row.paused_until,
+                    delivery: stored_delivery(&row.delivery, row.digest_time.as_deref()),
+                },
+            })
+            .collect())
+    }
+
+    pub(super) async fn get_filter(&self, chat_id: i64) -> anyhow::Result<Filter> {
+        let row = sqlx::query!(
+            "SELECT min_price, max_price, min_area, min_rooms, interior FROM subscriber_filters WHERE chat_id = ?",
+            chat_id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .context("failed to get filter")?;
+
+        Ok(row
+            .map(|row| Filter {
+                min_price: row.min_price.map(|price| price as usize),
+                max_price: row.max_price.map(|price| price as usize),
+                min_area: row.min_area.map(|area| area as u32),
+                min_rooms: row.min_rooms.map(|rooms| rooms as u32),
+                interior: row.interior.and_then(|interior| interior.parse().ok()),
+            })
+            .unwrap_or_default())
+    }
+
+    pub(super) async fn save_filter(&self, chat_id: i64, filter: &Filter) -> anyhow::Result<()> {
+        Self::save_filter_with(&self.pool, chat_id, filter).await
+    }
+
+    async fn save_filter_with(
+        executor: impl SqliteExecutor<'_>,
+        chat_id: i64,
+        filter: &Filter,
+    ) -> anyhow::Result<()> {
+        let min_price = filter.min_price.map(|price| price as i64);
+        let max_price = filter.max_price.map(|price| price as i64);
+        let interior = filter.interior.map(|interior| interior.as_str());
+        sqlx::query!(
+            r#"INSERT INTO subscriber_filters (chat_id, min_price, max_price, min_area, min_rooms, interior)
+            VALUES (?, ?, ?, ?, ?, ?)
+            ON CONFLICT (chat_id) DO UPDATE SET
+                min_price = excluded.min_price,
+                max_price = excluded.max_price,
+                min_area = excluded.min_area,
+                min_rooms = excluded.min_rooms,
+                interior = excluded.interior"#,
+            chat_id,
+            min_price,
+            max_price,
+            filter.min_area,
+            filter.min_rooms,
+            interior,
+        )
+        .execute(executor)
+        .await
+        .context("failed to save filter")?;
+        Ok(())
+    }
+
+    pub(super) async fn save_region(&self, chat_id: i64, region: &Region) -> anyhow::Result<()> {
+        let geometry = region.to_geojson();
+        sqlx::query!(
+            r#"INSERT INTO regions (chat_id, name, geometry) VALUES (?, ?, ?)
+            ON CONFLICT (chat_id, name) DO UPDATE SET geometry = excluded.geometry"#,
+            chat_id,
+            region.name,
+            geometry,
+        )
+        .execute(&self.pool)
+        .await
+        .context("failed to save region")?;
+        Ok(())
+    }
+
+    pub(super) async fn list_region_names(&self, chat_id: i64) -> anyhow::Result<Vec<String>> {
+        let result = sqlx::query!(
+            "SELECT name FROM regions WHERE chat_id = ? ORDER BY name",
+            chat_id
+        )
+        .fetch_all(&self.pool)
+        .await
+        .context("failed to list regions")?;
+        Ok(result.into_iter().map(|row| row.name).collect())
+    }
+
+    /// Returns whether a region with that name existed.
+    pub(super) async fn remove_region(&self, chat_id: i64, name: &str) -> anyhow::Result<bool> {
+        let result = sqlx::query!(
+            "DELETE FROM regions WHERE chat_id = ? AND name = ?",
+            chat_id,
+            name
+        )
+        .execute(&self.pool)
+        .await
+        .context("failed to remove region")?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub(super) async fn save_point_of_interest(
+        &self,
+        chat_id: i64,
+        point: &PointOfInterest,
+    ) -> anyhow::Result<()> {
+        let latitude = point.location.y();
+        let longitude = point.location.x();
+        sqlx::query!(
+            r#"INSERT INTO points_of_interest (chat_id, name, latitude, longitude, max_distance)
+            VALUES (?, ?, ?, ?, ?)
+            ON CONFLICT (chat_id, name) DO UPDATE SET
+                latitude = excluded.latitude,
+                longitude = excluded.longitude,
+                max_distance = excluded.max_distance"#,
+            chat_id,
+            point.name,
+            latitude,
+            longitude,
+            point.max_distance,
+        )
+        .execute(&self.pool)
+        .await
+        .context("failed to save point of interest")?;
+        Ok(())
+    }
+
+    pub(super) async fn list_points_of_interest(
+        &self,
+        chat_id: i64,
+    ) -> anyhow::Result<Vec<PointOfInterest>> {
+        let result = sqlx::query!(
+            "SELECT name, latitude, longitude, max_distance FROM points_of_interest WHERE chat_id = ? ORDER BY name",
+            chat_id
+        )
+        .fetch_all(&self.pool)
+        .await
+        .context("failed to list points of interest")?;
+        Ok(result
+            .into_iter()
+            .map(|row| PointOfInterest {
+                name: row.name,
+                location: geo::Point::new(row.longitude, row.latitude),
+                max_distance: row.max_distance,
+            })
+            .collect())
+    }
+
+    /// Returns whether a point of interest with that name existed.
+    pub(super) async fn remove_point_of_interest(
+        &self,
+        chat_id: i64,
+        name: &str,
+    ) -> anyhow::Result<bool> {
+        let result = sqlx::query!(
+            "DELETE FROM points_of_interest WHERE chat_id = ? AND name = ?",
+            chat_id,
+            name
+        )
+        .execute(&self.pool)
+        .await
+        .context("failed to remove point of interest")?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub(super) async fn follow_city(&self, chat_id: i64, city: &str) -> anyhow::Result<()> {
+        sqlx::query!(
+            "INSERT OR IGNORE INTO subscriber_cities (chat_id, city) VALUES (?, ?)",
+            chat_id,
+            city,
+        )
+        .execute(&self.pool)
+        .await
+        .context("failed to follow city")?;
+        Ok(())
+    }
+
+    pub(super) async fn list_followed_cities(&self, chat_id: i64) -> anyhow::Result<Vec<String>> {
+        let result = sqlx::query!(
+            "SELECT city FROM subscriber_cities WHERE chat_id = ? ORDER BY city",
+            chat_id
+        )
+        .fetch_all(&self.pool)
+        .await
+        .context("failed to list followed cities")?;
+        Ok(result.into_iter().map(|row| row.city).collect())
+    }
+
+    /// Replace the cities the subscriber follows.
+    pub(super) async fn set_followed_cities(
+        &self,
+        chat_id: i64,
+        cities: &[String],
+    ) -> anyhow::Result<()> {
+        let mut transaction = self.pool.begin().await?;
+        sqlx::query!("DELETE FROM subscriber_cities WHERE chat_id = ?", chat_id)
+            .execute(&mut *transaction)
+            .await
+            .context("failed to clear followed cities")?;
+        for city in cities {
+            sqlx::query!(
+                "INSERT INTO subscriber_cities (chat_id, city) VALUES (?, ?)",
+                chat_id,
+                city,
+            )
+            .execute(&mut *transaction)
+            .await
+            .context("failed to follow city")?;
+        }
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    /// Returns whether the subscriber followed the city.
+    pub(super) async fn unfollow_city(&self, chat_id: i64, city: &str) -> anyhow::Result<bool> {
+        let result = sqlx::query!(
+            "DELETE FROM subscriber_cities WHERE chat_id = ? AND city = ?",
+            chat_id,
+            city,
+        )
+        .execute(&self.pool)
+        .await
+        .context("failed to unfollow city")?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Set the status of a property for a subscriber, or clear it if `status` is `None`.
+    pub(super) async fn set_property_status(
+        &self,
+        chat_id: i64,
+        property_id: i64,
+        status: Option<PropertyStatus>,
+    ) -> anyhow::Result<()> {
+        let Some(status) = status else {
+            sqlx::query!(
+                "DELETE FROM property_statuses WHERE chat_id = ? AND property_id = ?",
+                chat_id,
+                property_id
+            )
+            .execute(&self.pool)
+            .await
+            .context("failed to clear property status")?;
+            return Ok(());
+        };
+
+        let status = status.as_str();
+        let updated_at = chrono::Utc::now().timestamp();
+        sqlx::query!(
+            r#"INSERT INTO property_statuses (chat_id, property_id, status, updated_at)
+            VALUES (?, ?, ?, ?)
+            ON CONFLICT (chat_id, property_id) DO UPDATE SET
+                status = excluded.status,
+                updated_at = excluded.updated_at"#,
+            chat_id,
+            property_id,
+            status,
+            updated_at,
+        )
+        .execute(&self.pool)
+        .await
+        .context("failed to save property status")?;
+        Ok(())
+    }
+
+    pub(super) async fn get_property_status(
+        &self,
+        chat_id: i64,
+        property_id: i64,
+    ) -> anyhow::Result<Option<PropertyStatus>> {
+        let result = sqlx::query!(
+            "SELECT status FROM property_statuses WHERE chat_id = ? AND property_id = ?",
+            chat_id,
+            property_id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .context("failed to get property status")?;
+        Ok(result.and_then(|row| PropertyStatus::parse(&row.status)))
+    }
+
+    /// The properties a subscriber gave `status`, most recently updated first.
+    pub(super) async fn list_tracked_properties(
+        &self,
+        chat_id: i64,
+        status: PropertyStatus,
+    ) -> anyhow::Result<Vec<TrackedProperty>> {
+        let status = status.as_str();
+        let result = sqlx::query!(
+            r#"SELECT title, url, price, area, updated_at, delisted_at
+            FROM property_statuses
+            JOIN properties ON properties.id = property_statuses.property_id
+            WHERE chat_id = ? AND status = ?
+            ORDER BY updated_at DESC"#,
+            chat_id,
+            status
+        )
+        .fetch_all(&self.pool)
+        .await
+        .context("failed to list tracked properties")?;
+        Ok(result
+            .into_iter()
+            .map(|row| TrackedProperty {
+                title: row.title,
+                url: row.url,
+                price: row.price.map(|price| price as usize),
+                area: row.area.map(|area| area as u32),
+                updated_at: row.updated_at,
+                delisted: row.delisted_at.is_some(),
+            })
+            .collect())
+    }
+
+    /// The chats which hid a property, or the property it's a duplicate of.
+    pub(super) async fn chats_hiding(
+        &self,
+        property_id: i64,
+        original_id: Option<i64>,
+    ) -> anyhow::Result<Vec<i64>> {
+        let original_id = original_id.unwrap_or(property_id);
+        let result = sqlx::query!(
+            r#"SELECT DISTINCT chat_id FROM property_statuses
+            WHERE status = 'hidden' AND property_id IN (?, ?)"#,
+            property_id,
+            original_id
+        )
+        .fetch_all(&self.pool)
+        .await
+        .context("failed to list chats hiding property")?;
+        Ok(result.into_iter().map(|row| row.chat_id).collect())
+    }
+
+    /// Queue a property for the next digest of a subscriber,
+    /// unless the property it's a duplicate of is already queued.
+    pub(super) async fn queue_notification(
+        &self,
+        chat_id: i64,
+        property_id: i64,
+        original_id: Option<i64>,
+        queued_at: i64,
+    ) -> anyhow::Result<()> {
+        let original_id = original_id.unwrap_or(property_id);
+        sqlx::query!(
+            r#"INSERT OR IGNORE INTO queued_notifications (chat_id, property_id, queued_at)
+            SELECT ?, ?, ?
+            WHERE NOT EXISTS (
+                SELECT 1 FROM queued_notifications WHERE chat_id = ? AND property_id = ?
+            )"#,
+            chat_id,
+            property_id,
+            queued_at,
+            chat_id,
+            original_id,
+        )
+        .execute(&self.pool)
+        .await
+        .context("failed to queue notification")?;
+        Ok(())
+    }
+
+    /// Subscribers who get digests, and those who switched back to instant delivery
+    /// with notifications still queued.
+    pub(super) async fn list_digest_subscribers(&self) -> anyhow::Result<Vec<DigestSubscriber>> {
+        let result = sqlx::query!(
+            r#"SELECT chat_id, paused_until, delivery, digest_time, last_digest_at
+            FROM subscribers
+            WHERE delivery != 'instant'
+                OR EXISTS (
+                    SELECT 1 FROM queued_notifications
+                    WHERE queued_notifications.chat_id = subscribers.chat_id
+                )"#
+        )
+        .fetch_all(&self.pool)
+        .await
+        .context("failed to list digest subscribers")?;
+        Ok(result
+            .into_iter()
+            .map(|row| DigestSubscriber {
+                chat_id: row.chat_id,
+                state: SubscriberState {
+                    paused_until: row.paused_until,
+                    delivery: stored_delivery(&row.delivery, row.digest_time.as_deref()),
+                },
+                last_digest_at: row.last_digest_at.unwrap_or_default(),
+            })
+            .collect())
+    }
+
+    /// The properties queued for a subscriber, leaving out the ones which were delisted
+    /// or hidden in the meantime.
+    pub(super) async fn list_queued(&self, chat_id: i64) -> anyhow::Result<Vec<QueuedProperty>> {
+        let result = sqlx::query!(
+            r#"SELECT title, url, price, area, rooms
+            FROM queued_notifications
+            JOIN properties ON properties.id = queued_notifications.property_id
+            WHERE queued_notifications.chat_id = ?
+                AND delisted_at IS NULL
+                AND NOT EXISTS (
+                    SELECT 1 FROM property_statuses
+                    WHERE property_statuses.chat_id = queued_notifications.chat_id
+                        AND property_statuses.property_id = queued_notifications.property_id
+                        AND status = 'hidden'
+                )"#,
+            chat_id
+        )
+        .fetch_all(&self.pool)
+        .await
+        .context("failed to list queued notifications")?;
+        Ok(result
+            .into_iter()
+            .map(|row| QueuedProperty {
+                title: row.title,
+                url: row.url,
+                price: row.price.unwrap_or_default() as usize,
+                area: row.area.unwrap_or_default() as u32,
+                rooms: row.rooms.map(|rooms| rooms as u32),
+            })
+            .collect())
+    }
+
+    /// Record the digest of a subscriber at `sent_at`, clearing their queue if it was `sent`.
+    /// Properties queued after `sent_at` stay queued for the next digest, and so does
+    /// everything if it couldn't be sent.
+    pub(super) async fn finish_digest(
+        &self,
+        chat_id: i64,
+        sent_at: i64,
+        sent: bool,
+    ) -> anyhow::Result<()> {
+        let mut transaction = self.pool.begin().await?;
+        if sent {
+            sqlx::query!(
+                "DELETE FROM queued_notifications WHERE chat_id = ? AND queued_at <= ?",
+                chat_id,
+                sent_at
+            )
+            .execute(&mut *transaction)
+            .await
+            .context("failed to clear queued notifications")?;
+        }
+        sqlx::query!(
+            "UPDATE subscribers SET last_digest_at = ? WHERE chat_id = ?",
+            sent_at,
+            chat_id
+        )
+        .execute(&mut *transaction)
+        .await
+        .context("failed to update subscriber")?;
+        transaction.commit().await?;
+        Ok(())
+    }
+}
+
+/// Subscribers whose stored delivery can't be parsed get instant notifications,
+/// rather than none at all.
+fn stored_delivery(mode: &str, time: Option<&str>) -> Delivery {
+    Delivery::from_stored(mode, time).unwrap_or(Delivery::Instant)
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    use super::*;
+    use crate::{
+        health::{ScrapeRun, ScrapeStats},
+        scraping::{FullScrapeResult, PartialScrapeResult},
+    };
+
+    const CHAT_ID: i64 = 42;
+
+    /// A fresh database, which lives as long as its only connection.
+    async fn in_memory() -> Persistence {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::migrate!().run(&pool).await.unwrap();
+        Persistence { pool }
+    }
+
+    fn property(url: &str, title: &str, price: usize) -> ScrapeResult {
+        ScrapeResult::Full(FullScrapeResult {
+            partial: PartialScrapeResult {
+                title: title.to_string(),
+                price,
+                url: url.to_string(),
+                area: 75,
+                postcode: Some("3039 JK".to_string()),
+                ..Default::default()
+            },
+            location: geo::Point::new(4.4662, 51.9316),
+        })
+    }
+
+    fn listing(url: &str, price: usize) -> PartialScrapeResult {
+        PartialScrapeResult {
+            title: "Statenweg 120".to_string(),
+            price,
+            url: url.to_string(),
+            area: 75,
+            ..Default::default()
+        }
+    }
+
+    async fn delisted(persistence: &Persistence) -> Vec<String> {
+        sqlx::query_scalar("SELECT url FROM properties WHERE delisted_at IS NOT NULL ORDER BY url")
+            .fetch_all(&persistence.pool)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn saving_a_known_property_updates_it() {
+        let persistence = in_memory().await;
+        let url = "https://www.verra.nl/statenweg-120/";
+        // The property page has more details than listing pages
+        let scraped = ScrapeResult::Partial(PartialScrapeResult {
+            rooms: Some(3),
+            postcode: Some("3039 JK".to_string()),
+            ..listing(url, 1495)
+        });
+        persistence
+            .save_property(&Website::Verra, "rotterdam", &scraped, 0)
+            .await
+            .unwrap();
+        persistence
+            .save_property(
+                &Website::Pararius,
+                "schiedam",
+                &ScrapeResult::Partial(listing(url, 1450)),
+                10,
+            )
+            .await
+            .unwrap();
+
+        let saved: (String, String, i64, Option<i64>, Option<String>, i64) = sqlx::query_as(
+            "SELECT site, city, price, rooms, postcode, last_seen_at FROM properties WHERE url = ?",
+        )
+        .bind(url)
+        .fetch_one(&persistence.pool)
+        .await
+        .unwrap();
+        assert_eq!(
+            saved,
+            (
+                "pararius".to_string(),
+                "schiedam".to_string(),
+                1450,
+                Some(3),
+                Some("3039 JK".to_string()),
+                10
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn delists_properties_newer_than_the_oldest_one_seen() {
+        let persistence = in_memory().await;
+        let save = |site, url: &'static str, seen_at| {
+            let persistence = persistence.clone();
+            async move {
+                persistence
+                    .save_property(
+                        &site,
+                        "rotterdam",
+                        &ScrapeResult::Partial(listing(url, 1495)),
+                        seen_at,
+                    )
+                    .await
+                    .unwrap();
+            }
+        };
+        save(Website::Verra, "https://www.verra.nl/oldest/", 0).await;
+        save(Website::Verra, "https://www.verra.nl/older/", 10).await;
+        save(Website::Verra, "https://www.verra.nl/newer/", 20).await;
+        save(Website::Verra, "https://www.verra.nl/newest/", 30).await;
+        save(Website::Pararius, "https://www.pararius.com/elsewhere/", 20).await;
+
+        save(Website::Verra, "https://www.verra.nl/older/", 100).await;
+        save(Website::Verra, "https://www.verra.nl/newest/", 100).await;
+        let count = persistence
+            .mark_delisted(&Website::Verra, "rotterdam", 100, 100)
+            .await
+            .unwrap();
+
+        // The oldest one may just have been pushed off the listing page
+        assert_eq!(count, 1);
+        assert_eq!(
+            delisted(&persistence).await,
+            vec!["https://www.verra.nl/newer/"]
+        );
+
+        // Relisted
+        save(Website::Verra, "https://www.verra.nl/newer/", 200).await;
+        assert!(delisted(&persistence).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn hidden_duplicates_stay_hidden_after_price_change() {
+        let persistence = in_memory().await;
+        persistence
+            .add_subscriber(CHAT_ID, &Filter::default())
+            .await
+            .unwrap();
+        let original = property("https://www.verra.nl/statenweg-120/", "Statenweg 120", 1495);
+        let original_id = persistence
+            .save_property(&Website::Verra, "rotterdam", &original, 0)
+            .await
+            .unwrap();
+        let duplicate = property("https://www.funda.nl/statenweg-120/", "Statenweg 120", 1495);
+        let duplicate_id = persistence
+            .save_property(&Website::Funda, "rotterdam", &duplicate, 10)
+            .await
+            .unwrap();
+        persistence
+            .set_duplicate_of(duplicate_id, original_id)
+            .await
+            .unwrap();
+        // The notification about the duplicate was folded, so its buttons hide the original
+        persistence
+            .set_property_status(CHAT_ID, original_id, Some(PropertyStatus::Hidden))
+            .await
+            .unwrap();
+
+        let cheaper = property("https://www.funda.nl/statenweg-120/", "Statenweg 120", 1450);
+        let known = persistence.list_properties().await.unwrap();
+        let known = &known["https://www.funda.nl/statenweg-120/"];
+        assert_eq!(known.duplicate_of, Some(original_id));
+        let property_id = persistence
+            .save_property(&Website::Funda, "rotterdam", &cheaper, 20)
+            .await
+            .unwrap();
+        assert_eq!(property_id, duplicate_id);
+        assert_eq!(
+            persistence
+                .chats_hiding(property_id, known.duplicate_of)
+                .await
+                .unwrap(),
+            vec![CHAT_ID]
+        );
+    }
+
+    #[tokio::test]
+    async fn summarizes_scrape_runs() {
+        let persistence = in_memory().await;
+        let runs = [
+            (
+                0,
+                Ok(ScrapeStats {
+                    listings: 10,
+                    parse_errors: 0,
+                    new_listings: 2,
+                }),
+            ),
+            (
+                300,
+                Ok(ScrapeStats {
+                    listings: 10,
+                    parse_errors: 1,
+                    new_listings: 0,
+                }),
+            ),
+            (600, Err("timed out".to_string())),
+        ];
+        for (started_at, outcome) in runs {
+            let run = ScrapeRun {
+                started_at,
+                finished_at: started_at + 30,
+                outcome,
+            };
+            persistence
+                .save_scrape_run(&Website::Pararius, &run)
+                .await
+                .unwrap();
+        }
+
+        let history = persistence
+            .scrape_history(&Website::Pararius, 2)
+            .await
+            .unwrap();
+        assert_eq!(history.recent.len(), 2);
+        assert_eq!(history.recent[0].as_ref().unwrap_err(), "timed out");
+        assert_eq!(history.recent[1].as_ref().unwrap().parse_errors, 1);
+        assert_eq!(history.first_run_at, Some(0));
+        assert_eq!(history.last_run_at, Some(630));
+        assert_eq!(history.last_new_listing_at, Some(0));
+
+        let history = persistence
+            .scrape_history(&Website::Verra, 2)
+            .await
+            .unwrap();
+        assert!(history.recent.is_empty());
+        assert_eq!(history.last_run_at, None);
     }
 }
@@ -0,0 +1,339 @@
+use anyhow::Context;
+use futures::future::BoxFuture;
+use reqwest::Url;
+use scraper::{ElementRef, Html, Selector};
+
+use super::{
+    http::HttpClient,
+    utils::{
+        image_url, leading_number, next_page_url, parse_euros, parse_listings, parse_postcode,
+        SelectExt,
+    },
+    Availability, FullScrapeResult, Listings, PartialScrapeResult, ScrapeResult, Search,
+    WebsiteScraper,
+};
+
+/// Funda lists parking spaces and storage units for rent as well, the last part of their
+/// links starts with the type of property.
+const NOT_HOMES: [&str; 2] = ["/parkeergelegenheid-", "/berging-"];
+
+pub struct FundaScraper {
+    client: HttpClient,
+    search: Search,
+    listing_selector: Selector,
+    next_page_selector: Selector,
+    link_selector: Selector,
+    title_selector: Selector,
+    postcode_selector: Selector,
+    price_selector: Selector,
+    features_selector: Selector,
+    image_selector: Selector,
+    // These are only on the property page
+    latitude_selector: Selector,
+    longitude_selector: Selector,
+    terms_selector: Selector,
+}
+
+impl FundaScraper {
+    pub fn new(client: HttpClient, search: Search) -> Self {
+        Self {
+            client,
+            search,
+            listing_selector: Selector::parse("[data-test-id=search-result-item]").unwrap(),
+            next_page_selector: Selector::parse(".pagination a[rel=next]").unwrap(),
+            link_selector: Selector::parse("a[data-test-id=object-street-link]").unwrap(),
+            title_selector: Selector::parse("[data-test-id=street-name-house-number]").unwrap(),
+            postcode_selector: Selector::parse("[data-test-id=postal-code-city]").unwrap(),
+            price_selector: Selector::parse("[data-test-id=price-rent]").unwrap(),
+            features_selector: Selector::parse("ul li").unwrap(),
+            image_selector: Selector::parse("img[data-test-id=object-image]").unwrap(),
+            latitude_selector: Selector::parse("meta[itemprop=latitude]").unwrap(),
+            longitude_selector: Selector::parse("meta[itemprop=longitude]").unwrap(),
+            terms_selector: Selector::parse("dl.object-kenmerken-list dt").unwrap(),
+        }
+    }
+
+    fn is_home(&self, listing: &ElementRef) -> bool {
+        let href = listing
+            .select_one(&self.link_selector)
+            .ok()
+            .and_then(|link| link.attr("href"))
+            .unwrap_or_default();
+        !NOT_HOMES.iter().any(|prefix| href.contains(prefix))
+    }
+
+    /// The description next to the term `label` in the features on the property page.
+    fn feature<'a>(&self, document: &'a Html, label: &str) -> Option<&'a str> {
+        let term = document
+            .select(&self.terms_selector)
+            .find(|term| term.text().any(|text| text.trim() == label))?;
+        let description = term.next_siblings().find_map(ElementRef::wrap)?;
+        description
+            .text()
+            .map(str::trim)
+            .find(|text| !text.is_empty())
+    }
+}
+
+impl WebsiteScraper for FundaScraper {
+    fn list_properties(&self) -> BoxFuture<'_, anyhow::Result<Listings>> {
+        // The filters are JSON values, hence the quotes
+        let mut query = vec![("selected_area", format!("[\"{}\"]", self.search.city))];
+        if let Some(price_range) = self.search.price_range() {
+            query.push(("price", format!("\"{price_range}\"")));
+        }
+        query.push(("sort", "\"date_down\"".to_string()));
+
+        let url = Url::parse_with_params("https://www.funda.nl/zoeken/huur", &query)
+            .expect("the base URL is valid");
+        self.list_page(url.to_string())
+    }
+
+    fn list_page(&self, url: String) -> BoxFuture<'_, anyhow::Result<Listings>> {
+        Box::pin(async move {
+            let response = self.client.get_text(&url).await?;
+
+            let document = Html::parse_document(&response);
+            let listings = document
+                .select(&self.listing_selector)
+                .filter(|listing| self.is_home(listing));
+
+            let mut listings = parse_listings(listings, |listing| {
+                let link = listing.select_one(&self.link_selector)?;
+                let href = link.attr("href").context("no link")?;
+                let url = Url::parse(&url)?.join(href)?.to_string();
+
+                let title = listing.select_one_text(&self.title_selector)?.to_string();
+                let postcode = listing
+                    .select_one_text(&self.postcode_selector)
+                    .ok()
+                    .and_then(parse_postcode);
+
+                // e.g. "€ 1.475 /maand"
+                let raw_price = listing.select_one_text(&self.price_selector)?;
+                let price = parse_euros(raw_price)
+                    .with_context(|| format!("invalid price: {raw_price}"))?;
+
+                // The features are only icons with a value, e.g. "67 m²", "2" (bedrooms), "C"
+                let features: Vec<&str> = listing
+                    .select(&self.features_selector)
+                    .filter_map(|feature| {
+                        feature.text().map(str::trim).find(|text| !text.is_empty())
+                    })
+                    .collect();
+                let area = features
+                    .iter()
+                    .find(|feature| feature.ends_with("m²"))
+                    .and_then(|area| leading_number(area))
+                    .context("no area")?;
+                let bedrooms = features.iter().find_map(|feature| feature.parse().ok());
+                let energy_label = features
+                    .iter()
+                    .find(|feature| is_energy_label(feature))
+                    .map(|label| label.to_string());
+                let image_url = image_url(&listing, &self.image_selector, &url);
+
+                anyhow::Ok(ScrapeResult::Partial(PartialScrapeResult {
+                    title,
+                    price,
+                    url,
+                    area,
+                    bedrooms,
+                    energy_label,
+                    postcode,
+                    image_url,
+                    ..Default::default()
+                }))
+            });
+            listings.next_page = next_page_url(&document, &self.next_page_selector, &url);
+            Ok(listings)
+        })
+    }
+
+    fn scrape_property(
+        &self,
+        mut partial: PartialScrapeResult,
+    ) -> BoxFuture<'_, anyhow::Result<FullScrapeResult>> {
+        Box::pin(async move {
+            let response = self
+                .client
+                .get_text(&partial.url)
+                .await
+                .with_context(|| format!("failed to GET property at {}", partial.url))?;
+
+            let document = Html::parse_document(&response);
+            let latitude: f64 = document
+                .select_one(&self.latitude_selector)?
+                .attr("content")
+                .context("no latitude")?
+                .parse()
+                .context("invalid latitude")?;
+            let longitude: f64 = document
+                .select_one(&self.longitude_selector)?
+                .attr("content")
+                .context("no longitude")?
+                .parse()
+                .context("invalid longitude")?;
+
+            // e.g. "3 kamers (2 slaapkamers)"
+            partial.rooms = self
+                .feature(&document, "Aantal kamers")
+                .and_then(leading_number);
+            partial.interior = self
+                .feature(&document, "Specificaties")
+                .and_then(|interior| interior.parse().ok());
+            partial.deposit = self.feature(&document, "Waarborgsom").and_then(parse_euros);
+            partial.available_from = self
+                .feature(&document, "Aanvaarding")
+                .and_then(Availability::parse_text);
+
+            Ok(FullScrapeResult {
+                partial,
+                location: geo::Point::new(longitude, latitude),
+            })
+        })
+    }
+}
+
+/// Whether `text` is an energy label like "C" or "A+++".
+fn is_energy_label(text: &str) -> bool {
+    let mut chars = text.chars();
+    let has_class = chars.next().is_some_and(|c| ('A'..='G').contains(&c));
+    let pluses = chars.as_str();
+    has_class && pluses.len() <= 4 && pluses.chars().all(|c| c == '+')
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::scraping::{
+        testing::{rotterdam, stand_in},
+        Interior,
+    };
+
+    const LISTINGS: &str = include_str!("../../tests/fixtures/funda/listings.html");
+    const PROPERTY: &str = include_str!("../../tests/fixtures/funda/property.html");
+
+    #[tokio::test]
+    async fn lists_properties() {
+        let client = stand_in(&[(
+            "/zoeken/huur?selected_area=%5B%22rotterdam%22%5D&sort=%22date_down%22",
+            LISTINGS,
+        )])
+        .await;
+        let listings = FundaScraper::new(client, rotterdam())
+            .list_properties()
+            .await
+            .unwrap();
+
+        assert_eq!(
+            listings.properties,
+            vec![
+                ScrapeResult::Partial(PartialScrapeResult {
+                    title: "Oudedijk 110 B".to_string(),
+                    price: 1475,
+                    url: "https://www.funda.nl/detail/huur/rotterdam/appartement-oudedijk-110-b/43518765/"
+                        .to_string(),
+                    area: 67,
+                    bedrooms: Some(2),
+                    energy_label: Some("C".to_string()),
+                    postcode: Some("3061 AM".to_string()),
+                    image_url: Some(
+                        "https://cloud.funda.nl/valentina_media/190/218/431_360x240.jpg".to_string()
+                    ),
+                    ..Default::default()
+                }),
+                ScrapeResult::Partial(PartialScrapeResult {
+                    title: "Voorschoterlaan 8".to_string(),
+                    price: 2150,
+                    url: "https://www.funda.nl/detail/huur/rotterdam/huis-voorschoterlaan-8/43522310/"
+                        .to_string(),
+                    area: 118,
+                    bedrooms: Some(4),
+                    energy_label: Some("A+".to_string()),
+                    postcode: Some("3062 KK".to_string()),
+                    image_url: Some(
+                        "https://cloud.funda.nl/valentina_media/190/301/112_360x240.jpg".to_string()
+                    ),
+                    ..Default::default()
+                }),
+            ]
+        );
+        // The parking space isn't a home, rather than a listing that failed to parse
+        assert!(listings.errors.is_empty());
+        assert_eq!(
+            listings.next_page.as_deref(),
+            Some("https://www.funda.nl/zoeken/huur?selected_area=%5B%22rotterdam%22%5D&sort=%22date_down%22&search_result=2")
+        );
+    }
+
+    #[test]
+    fn recognizes_energy_labels() {
+        for label in ["A", "C", "G", "A+", "A++++"] {
+            assert!(is_energy_label(label), "{label}");
+        }
+        for text in ["H", "A+++++", "Appartement", "Bovenwoning", "2", ""] {
+            assert!(!is_energy_label(text), "{text}");
+        }
+    }
+
+    #[tokio::test]
+    async fn searches_price_range() {
+        let client = stand_in(&[(
+            "/zoeken/huur?selected_area=%5B%22den-haag%22%5D&price=%221000-1500%22&sort=%22date_down%22",
+            LISTINGS,
+        )])
+        .await;
+        let search = Search {
+            city: "den-haag".to_string(),
+            min_price: Some(1000),
+            max_price: Some(1500),
+        };
+
+        let listings = FundaScraper::new(client, search)
+            .list_properties()
+            .await
+            .unwrap();
+        assert_eq!(listings.properties.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn scrapes_property() {
+        let client = stand_in(&[(
+            "/detail/huur/rotterdam/appartement-oudedijk-110-b/43518765/",
+            PROPERTY,
+        )])
+        .await;
+        let partial = PartialScrapeResult {
+            title: "Oudedijk 110 B".to_string(),
+            price: 1475,
+            url: "https://www.funda.nl/detail/huur/rotterdam/appartement-oudedijk-110-b/43518765/"
+                .to_string(),
+            area: 67,
+            ..Default::default()
+        };
+
+        let full = FundaScraper::new(client, rotterdam())
+            .scrape_property(partial.clone())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            full,
+            FullScrapeResult {
+                partial: PartialScrapeResult {
+                    rooms: Some(3),
+                    interior: Some(Interior::Upholstered),
+                    deposit: Some(2950),
+                    available_from: Some(Availability::From(
+                        NaiveDate::from_ymd_opt(2025, 4, 1).unwrap()
+                    )),
+                    ..partial
+                },
+                location: geo::Point::new(4.5003, 51.9214),
+            }
+        );
+    }
+}
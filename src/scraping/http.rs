@@ -0,0 +1,308 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::Context;
+use reqwest::{header::RETRY_AFTER, Method, Response, StatusCode, Url};
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::time::Instant;
+
+use crate::config::HttpConfig;
+
+/// Never wait longer than this between retries, whatever the server asks for.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(120);
+
+/// An HTTP client shared by all scrapers, so they share a connection pool and play nice
+/// with the websites: requests to the same host are spaced out, and requests failing
+/// because of rate limiting or server errors are retried with exponential backoff.
+#[derive(Clone)]
+pub struct HttpClient {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    client: reqwest::Client,
+    max_retries: u32,
+    retry_backoff: Duration,
+    min_request_interval: Duration,
+    redirect: Option<Url>,
+    /// When the next request to each host may be sent.
+    next_request: Mutex<HashMap<String, Instant>>,
+    /// Responses which may be reused, see [`HttpClient::get_text_cached`].
+    cached: tokio::sync::Mutex<HashMap<String, (Instant, String)>>,
+}
+
+impl HttpClient {
+    pub fn new(config: &HttpConfig) -> anyhow::Result<Self> {
+        Self::build(config, None)
+    }
+
+    /// Send all requests to the scheme, host and port of `base` instead of the requested ones,
+    /// e.g. to test scrapers against a local stand-in for the website.
+    pub fn redirected(config: &HttpConfig, base: Url) -> anyhow::Result<Self> {
+        Self::build(config, Some(base))
+    }
+
+    fn build(config: &HttpConfig, redirect: Option<Url>) -> anyhow::Result<Self> {
+        let client = reqwest::Client::builder()
+            .user_agent(&config.user_agent)
+            .timeout(Duration::from_secs(config.timeout_secs))
+            .build()
+            .context("failed to build HTTP client")?;
+
+        Ok(Self {
+            inner: Arc::new(Inner {
+                client,
+                max_retries: config.max_retries,
+                retry_backoff: Duration::from_millis(config.retry_backoff_ms),
+                min_request_interval: Duration::from_millis(config.min_request_interval_ms),
+                redirect,
+                next_request: Mutex::new(HashMap::new()),
+                cached: tokio::sync::Mutex::new(HashMap::new()),
+            }),
+        })
+    }
+
+    pub async fn get_text(&self, url: &str) -> anyhow::Result<String> {
+        Ok(self.get(url).await?.text().await?)
+    }
+
+    /// GET a URL as text, reusing the response to a request for it less than `max_age` ago.
+    /// For feeds which several scrapers list from, so they're only requested once.
+    pub async fn get_text_cached(&self, url: &str, max_age: Duration) -> anyhow::Result<String> {
+        // Held during the request, so concurrent requests for the URL wait for its response
+        let mut cached = self.inner.cached.lock().await;
+        if let Some((fetched_at, text)) = cached.get(url) {
+            if fetched_at.elapsed() < max_age {
+                return Ok(text.clone());
+            }
+        }
+
+        let text = self.get_text(url).await?;
+        cached.insert(url.to_string(), (Instant::now(), text.clone()));
+        Ok(text)
+    }
+
+    pub async fn get_json<T: DeserializeOwned>(&self, url: &str) -> anyhow::Result<T> {
+        Ok(self.get(url).await?.json().await?)
+    }
+
+    /// POST `body` as JSON, for websites which search with an API.
+    pub async fn post_json<B: Serialize, T: DeserializeOwned>(
+        &self,
+        url: &str,
+        body: &B,
+    ) -> anyhow::Result<T> {
+        Ok(self
+            .send(Method::POST, url, Some(body))
+            .await?
+            .json()
+            .await?)
+    }
+
+    /// GET a URL, retrying on rate limiting, server errors and timeouts.
+    /// Other unsuccessful statuses are returned as errors.
+    pub async fn get(&self, url: &str) -> anyhow::Result<Response> {
+        self.send::<()>(Method::GET, url, None).await
+    }
+
+    async fn send<B: Serialize>(
+        &self,
+        method: Method,
+        url: &str,
+        body: Option<&B>,
+    ) -> anyhow::Result<Response> {
+        let mut url = Url::parse(url).with_context(|| format!("invalid URL {url}"))?;
+        if let Some(redirect) = &self.inner.redirect {
+            let redirected = url.set_scheme(redirect.scheme()).is_ok()
+                && url.set_host(redirect.host_str()).is_ok()
+                && url.set_port(redirect.port()).is_ok();
+            anyhow::ensure!(redirected, "failed to redirect {url} to {redirect}");
+        }
+        let host = url.host_str().unwrap_or_default().to_string();
+
+        let mut attempt = 0;
+        loop {
+            self.wait_for_turn(&host).await;
+            let mut request = self.inner.client.request(method.clone(), url.clone());
+            if let Some(body) = body {
+                request = request.json(body);
+            }
+            let result = request.send().await;
+
+            let retry_after = match &result {
+                Ok(response) if is_retryable(response.status()) => retry_after(response),
+                Err(error) if error.is_timeout() || error.is_connect() => None,
+                _ => return Ok(result?.error_for_status()?),
+            };
+
+            if attempt >= self.inner.max_retries {
+                return Ok(result?.error_for_status()?);
+            }
+
+            let delay = retry_after
+                .unwrap_or_else(|| self.backoff(attempt))
+                .min(MAX_RETRY_DELAY);
+            tracing::warn!("Request to {url} failed, retrying in {delay:?}");
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// The delay before retry number `attempt + 1`, without a Retry-After header.
+    fn backoff(&self, attempt: u32) -> Duration {
+        2u32.checked_pow(attempt)
+            .map_or(MAX_RETRY_DELAY, |factor| {
+                self.inner.retry_backoff.saturating_mul(factor)
+            })
+            .min(MAX_RETRY_DELAY)
+    }
+
+    /// Wait until we're allowed to send a request to `host`.
+    async fn wait_for_turn(&self, host: &str) {
+        let turn = {
+            let mut next_request = self.inner.next_request.lock().unwrap();
+            let now = Instant::now();
+            let next = next_request.entry(host.to_string()).or_insert(now);
+            let turn = (*next).max(now);
+            // Reserve our turn, so concurrent requests queue up behind it
+            *next = turn + self.inner.min_request_interval;
+            turn
+        };
+
+        tokio::time::sleep_until(turn).await;
+    }
+}
+
+fn is_retryable(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// Only the delay-seconds form of Retry-After is supported, websites rarely send a date.
+fn retry_after(response: &Response) -> Option<Duration> {
+    let seconds = response.headers().get(RETRY_AFTER)?.to_str().ok()?;
+    Some(Duration::from_secs(seconds.trim().parse().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scraping::testing::scripted;
+
+    fn client(base: Url, max_retries: u32, min_request_interval_ms: u64) -> HttpClient {
+        let config = HttpConfig {
+            max_retries,
+            retry_backoff_ms: 10,
+            min_request_interval_ms,
+            ..Default::default()
+        };
+        HttpClient::redirected(&config, base).unwrap()
+    }
+
+    #[tokio::test]
+    async fn retries_server_errors() {
+        let (base, requests) = scripted(&[
+            ("503 Service Unavailable", ""),
+            ("502 Bad Gateway", ""),
+            ("200 OK", "listings"),
+        ])
+        .await;
+
+        let text = client(base, 2, 0)
+            .get_text("https://www.pararius.com/apartments/rotterdam")
+            .await
+            .unwrap();
+        assert_eq!(text, "listings");
+        assert_eq!(requests.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_retries() {
+        let (base, requests) = scripted(&[
+            ("500 Internal Server Error", ""),
+            ("500 Internal Server Error", ""),
+            ("200 OK", "listings"),
+        ])
+        .await;
+
+        let result = client(base, 1, 0)
+            .get_text("https://www.pararius.com/apartments/rotterdam")
+            .await;
+        assert!(result.is_err());
+        assert_eq!(requests.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn waits_as_long_as_retry_after_asks() {
+        let (base, requests) = scripted(&[
+            ("429 Too Many Requests\r\nRetry-After: 1", ""),
+            ("200 OK", "listings"),
+        ])
+        .await;
+
+        client(base, 1, 0)
+            .get_text("https://www.pararius.com/apartments/rotterdam")
+            .await
+            .unwrap();
+        let requests = requests.lock().unwrap();
+        assert!(requests[1] - requests[0] >= Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn spaces_out_requests_to_the_same_host() {
+        let (base, requests) = scripted(&[("200 OK", ""), ("200 OK", ""), ("200 OK", "")]).await;
+        let client = client(base, 0, 200);
+
+        futures::future::try_join_all((0..3).map(|page| {
+            let client = client.clone();
+            async move {
+                client
+                    .get(&format!(
+                        "https://www.pararius.com/apartments/rotterdam/page-{page}"
+                    ))
+                    .await
+            }
+        }))
+        .await
+        .unwrap();
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 3);
+        let mut requests = requests.clone();
+        requests.sort();
+        for pair in requests.windows(2) {
+            // The reserved turns are 200 ms apart, a request may be sent a bit late though
+            assert!(pair[1] - pair[0] >= Duration::from_millis(150));
+        }
+    }
+
+    #[tokio::test]
+    async fn reuses_recent_responses() {
+        let (base, requests) = scripted(&[("200 OK", "feed"), ("200 OK", "newer feed")]).await;
+        let client = client(base, 0, 0);
+        let url = "https://www.woonnetrijnmond.nl/api/aanbod";
+
+        for _ in 0..2 {
+            let text = client
+                .get_text_cached(url, Duration::from_secs(60))
+                .await
+                .unwrap();
+            assert_eq!(text, "feed");
+        }
+        assert_eq!(requests.lock().unwrap().len(), 1);
+
+        let text = client.get_text_cached(url, Duration::ZERO).await.unwrap();
+        assert_eq!(text, "newer feed");
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_maximum() {
+        let client = client(Url::parse("http://localhost").unwrap(), 40, 0);
+        assert_eq!(client.backoff(0), Duration::from_millis(10));
+        assert_eq!(client.backoff(3), Duration::from_millis(80));
+        assert_eq!(client.backoff(20), MAX_RETRY_DELAY);
+        // 2^40 doesn't fit in a u32
+        assert_eq!(client.backoff(40), MAX_RETRY_DELAY);
+    }
+}
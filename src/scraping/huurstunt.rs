@@ -0,0 +1,289 @@
+use anyhow::Context;
+use futures::future::BoxFuture;
+use reqwest::Url;
+use scraper::{Html, Selector};
+
+use super::{
+    http::HttpClient,
+    utils::{
+        image_url, leading_number, next_page_url, parse_euros, parse_listings, parse_postcode,
+        SelectExt,
+    },
+    Availability, FullScrapeResult, Listings, PartialScrapeResult, ScrapeResult, Search,
+    WebsiteScraper,
+};
+
+pub struct HuurstuntScraper {
+    client: HttpClient,
+    search: Search,
+    listing_selector: Selector,
+    next_page_selector: Selector,
+    link_selector: Selector,
+    title_selector: Selector,
+    location_selector: Selector,
+    price_selector: Selector,
+    features_selector: Selector,
+    image_selector: Selector,
+    // These are only on the property page
+    map_selector: Selector,
+    details_selector: Selector,
+    label_selector: Selector,
+    value_selector: Selector,
+}
+
+impl HuurstuntScraper {
+    pub fn new(client: HttpClient, search: Search) -> Self {
+        Self {
+            client,
+            search,
+            listing_selector: Selector::parse(".rental-card").unwrap(),
+            next_page_selector: Selector::parse("a.pagination__next").unwrap(),
+            link_selector: Selector::parse("a.rental-card__link").unwrap(),
+            title_selector: Selector::parse(".rental-card__title").unwrap(),
+            location_selector: Selector::parse(".rental-card__location").unwrap(),
+            price_selector: Selector::parse(".rental-card__price").unwrap(),
+            features_selector: Selector::parse(".rental-card__features li").unwrap(),
+            image_selector: Selector::parse("img.rental-card__image").unwrap(),
+            map_selector: Selector::parse("#object-map").unwrap(),
+            details_selector: Selector::parse(".rental-details tr").unwrap(),
+            label_selector: Selector::parse("th").unwrap(),
+            value_selector: Selector::parse("td").unwrap(),
+        }
+    }
+
+    /// The value in the row labelled `label` of the details on the property page.
+    fn detail<'a>(&self, document: &'a Html, label: &str) -> Option<&'a str> {
+        let row = document
+            .select(&self.details_selector)
+            .find(|row| row.select_one_text(&self.label_selector).ok() == Some(label))?;
+        let value = row.select(&self.value_selector).next()?;
+        value.text().map(str::trim).find(|text| !text.is_empty())
+    }
+}
+
+impl WebsiteScraper for HuurstuntScraper {
+    fn list_properties(&self) -> BoxFuture<'_, anyhow::Result<Listings>> {
+        let mut query = Vec::new();
+        if let Some(min_price) = self.search.min_price {
+            query.push(("prijs_min", min_price.to_string()));
+        }
+        if let Some(max_price) = self.search.max_price {
+            query.push(("prijs_max", max_price.to_string()));
+        }
+        query.push(("sorteer", "nieuwste".to_string()));
+
+        let url = Url::parse_with_params(
+            &format!("https://www.huurstunt.nl/huren/{}/", self.search.city),
+            &query,
+        )
+        .expect("the base URL is valid");
+        self.list_page(url.to_string())
+    }
+
+    fn list_page(&self, url: String) -> BoxFuture<'_, anyhow::Result<Listings>> {
+        Box::pin(async move {
+            let response = self.client.get_text(&url).await?;
+
+            let document = Html::parse_document(&response);
+            let listings = document.select(&self.listing_selector);
+
+            let mut listings = parse_listings(listings, |listing| {
+                let href = listing
+                    .select_one(&self.link_selector)?
+                    .attr("href")
+                    .context("no link")?;
+                let url = Url::parse(&url)?.join(href)?.to_string();
+
+                // e.g. "Appartement Goudsesingel", the street without a house number
+                let title = listing.select_one_text(&self.title_selector)?.to_string();
+                let postcode = listing
+                    .select_one_text(&self.location_selector)
+                    .ok()
+                    .and_then(parse_postcode);
+
+                // e.g. "€ 1.250 p/m"
+                let raw_price = listing.select_one_text(&self.price_selector)?;
+                let price = parse_euros(raw_price)
+                    .with_context(|| format!("invalid price: {raw_price}"))?;
+
+                // e.g. "55 m²", "2 kamers" and "Gemeubileerd", in no particular order
+                let features: Vec<&str> = listing
+                    .select(&self.features_selector)
+                    .filter_map(|feature| {
+                        feature.text().map(str::trim).find(|text| !text.is_empty())
+                    })
+                    .collect();
+                let area = features
+                    .iter()
+                    .find(|feature| feature.ends_with("m²"))
+                    .and_then(|area| leading_number(area))
+                    .context("no area")?;
+                let rooms = features
+                    .iter()
+                    .find(|feature| feature.contains("kamer"))
+                    .and_then(|rooms| leading_number(rooms));
+                let interior = features.iter().find_map(|feature| feature.parse().ok());
+                let image_url = image_url(&listing, &self.image_selector, &url);
+
+                anyhow::Ok(ScrapeResult::Partial(PartialScrapeResult {
+                    title,
+                    price,
+                    url,
+                    area,
+                    rooms,
+                    interior,
+                    postcode,
+                    image_url,
+                    ..Default::default()
+                }))
+            });
+            listings.next_page = next_page_url(&document, &self.next_page_selector, &url);
+            Ok(listings)
+        })
+    }
+
+    fn scrape_property(
+        &self,
+        mut partial: PartialScrapeResult,
+    ) -> BoxFuture<'_, anyhow::Result<FullScrapeResult>> {
+        Box::pin(async move {
+            let response = self
+                .client
+                .get_text(&partial.url)
+                .await
+                .with_context(|| format!("failed to GET property at {}", partial.url))?;
+
+            let document = Html::parse_document(&response);
+            let map = document.select_one(&self.map_selector)?;
+            let latitude: f64 = map
+                .attr("data-lat")
+                .context("no latitude")?
+                .parse()
+                .context("invalid latitude")?;
+            let longitude: f64 = map
+                .attr("data-lng")
+                .context("no longitude")?
+                .parse()
+                .context("invalid longitude")?;
+
+            partial.deposit = self.detail(&document, "Borg").and_then(parse_euros);
+            partial.available_from = self
+                .detail(&document, "Beschikbaar")
+                .and_then(Availability::parse_text);
+
+            Ok(FullScrapeResult {
+                partial,
+                location: geo::Point::new(longitude, latitude),
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scraping::{
+        testing::{rotterdam, stand_in},
+        Interior,
+    };
+
+    const LISTINGS: &str = include_str!("../../tests/fixtures/huurstunt/listings.html");
+    const PROPERTY: &str = include_str!("../../tests/fixtures/huurstunt/property.html");
+
+    #[tokio::test]
+    async fn lists_properties() {
+        let client = stand_in(&[("/huren/rotterdam/?sorteer=nieuwste", LISTINGS)]).await;
+        let listings = HuurstuntScraper::new(client, rotterdam())
+            .list_properties()
+            .await
+            .unwrap();
+
+        assert_eq!(
+            listings.properties,
+            vec![
+                ScrapeResult::Partial(PartialScrapeResult {
+                    title: "Appartement Goudsesingel".to_string(),
+                    price: 1250,
+                    url:
+                        "https://www.huurstunt.nl/huren/rotterdam/appartement/goudsesingel/a7f3k2/"
+                            .to_string(),
+                    area: 55,
+                    rooms: Some(2),
+                    interior: Some(Interior::Furnished),
+                    postcode: Some("3011 KD".to_string()),
+                    image_url: Some(
+                        "https://media.huurstunt.nl/rentals/a7f3k2/1-medium.jpg".to_string()
+                    ),
+                    ..Default::default()
+                }),
+                ScrapeResult::Partial(PartialScrapeResult {
+                    title: "Studio Schiedamseweg".to_string(),
+                    price: 895,
+                    url: "https://www.huurstunt.nl/huren/rotterdam/studio/schiedamseweg/b9d1m4/"
+                        .to_string(),
+                    area: 28,
+                    rooms: Some(1),
+                    ..Default::default()
+                }),
+            ]
+        );
+        // Without a price there's no way to filter it
+        assert_eq!(listings.errors.len(), 1);
+        assert!(listings.errors[0].html.contains("Lisstraat"));
+        assert_eq!(
+            listings.next_page.as_deref(),
+            Some("https://www.huurstunt.nl/huren/rotterdam/?pagina=2")
+        );
+    }
+
+    #[tokio::test]
+    async fn searches_city_and_price_range() {
+        let client =
+            stand_in(&[("/huren/utrecht/?prijs_max=1500&sorteer=nieuwste", LISTINGS)]).await;
+        let search = Search {
+            city: "utrecht".to_string(),
+            min_price: None,
+            max_price: Some(1500),
+        };
+
+        let listings = HuurstuntScraper::new(client, search)
+            .list_properties()
+            .await
+            .unwrap();
+        assert_eq!(listings.properties.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn scrapes_property() {
+        let client = stand_in(&[(
+            "/huren/rotterdam/appartement/goudsesingel/a7f3k2/",
+            PROPERTY,
+        )])
+        .await;
+        let partial = PartialScrapeResult {
+            title: "Appartement Goudsesingel".to_string(),
+            price: 1250,
+            url: "https://www.huurstunt.nl/huren/rotterdam/appartement/goudsesingel/a7f3k2/"
+                .to_string(),
+            area: 55,
+            ..Default::default()
+        };
+
+        let full = HuurstuntScraper::new(client, rotterdam())
+            .scrape_property(partial.clone())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            full,
+            FullScrapeResult {
+                partial: PartialScrapeResult {
+                    deposit: Some(2500),
+                    available_from: Some(Availability::Immediately),
+                    ..partial
+                },
+                location: geo::Point::new(4.4871, 51.9230),
+            }
+        );
+    }
+}
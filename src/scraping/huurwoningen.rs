@@ -1,100 +1,159 @@
 use anyhow::Context;
 use futures::future::BoxFuture;
-use itertools::Itertools;
 use scraper::{Html, Selector};
 
 use super::{
-    utils::SelectExt, FullScrapeResult, PartialScrapeResult, ScrapeResult, WebsiteScraper,
+    http::HttpClient,
+    utils::{
+        image_url, leading_number, next_page_url, parse_euros, parse_listings, parse_postcode,
+        SelectExt,
+    },
+    Availability, FullScrapeResult, Listings, PartialScrapeResult, ScrapeResult, Search,
+    WebsiteScraper, PRICE_ON_REQUEST,
 };
 
 pub struct HuurwoningenScraper {
+    client: HttpClient,
+    search: Search,
     houses_selector: Selector,
+    next_page_selector: Selector,
     price_selector: Selector,
     title_selector: Selector,
     area_selector: Selector,
     map_selector: Selector,
+    subtitle_selector: Selector,
+    rooms_selector: Selector,
+    interior_selector: Selector,
+    image_selector: Selector,
+    // Huurwoningen runs on the same platform as Pararius,
+    // so the property pages have the same feature list.
+    bedrooms_selector: Selector,
+    deposit_selector: Selector,
+    energy_label_selector: Selector,
+    available_selector: Selector,
 }
 
-impl Default for HuurwoningenScraper {
-    fn default() -> Self {
+impl HuurwoningenScraper {
+    pub fn new(client: HttpClient, search: Search) -> Self {
         Self {
+            client,
+            search,
             houses_selector: Selector::parse("li.search-list__item--listing").unwrap(),
+            next_page_selector: Selector::parse("a.pagination__link--next").unwrap(),
             price_selector: Selector::parse("div.listing-search-item__price").unwrap(),
             title_selector: Selector::parse("a.listing-search-item__link--title").unwrap(),
             area_selector: Selector::parse("li.illustrated-features__item--surface-area").unwrap(),
             map_selector: Selector::parse("wc-detail-map").unwrap(),
+            subtitle_selector: Selector::parse("div.listing-search-item__sub-title").unwrap(),
+            rooms_selector: Selector::parse("li.illustrated-features__item--number-of-rooms")
+                .unwrap(),
+            interior_selector: Selector::parse("li.illustrated-features__item--interior").unwrap(),
+            image_selector: Selector::parse("img.picture__image").unwrap(),
+            bedrooms_selector: Selector::parse(
+                "dd.listing-features__description--number_of_bedrooms",
+            )
+            .unwrap(),
+            deposit_selector: Selector::parse("dd.listing-features__description--deposit").unwrap(),
+            energy_label_selector: Selector::parse(
+                "dd.listing-features__description--energy_label",
+            )
+            .unwrap(),
+            available_selector: Selector::parse("dd.listing-features__description--available")
+                .unwrap(),
         }
     }
 }
 
 impl WebsiteScraper for HuurwoningenScraper {
-    fn list_properties(&self) -> BoxFuture<anyhow::Result<Vec<ScrapeResult>>> {
-        Box::pin(async {
-            let response = reqwest::get("https://www.huurwoningen.nl/in/rotterdam/")
-                .await?
-                .error_for_status()?
-                .text()
-                .await?;
+    fn list_properties(&self) -> BoxFuture<'_, anyhow::Result<Listings>> {
+        let mut url = format!("https://www.huurwoningen.nl/in/{}/", self.search.city);
+        if let Some(price_range) = self.search.price_range() {
+            url.push_str(&format!("?price={price_range}"));
+        }
+        self.list_page(url)
+    }
+
+    fn list_page(&self, url: String) -> BoxFuture<'_, anyhow::Result<Listings>> {
+        Box::pin(async move {
+            let response = self.client.get_text(&url).await?;
 
             let document = Html::parse_document(&response);
             let houses = document.select(&self.houses_selector);
 
-            Ok(houses
-                .into_iter()
-                .map(|house| {
-                    let price_raw = house
-                        .select_one_text(&self.price_selector)
-                        .context("no price")?;
-
-                    let price: usize = if price_raw == "Prijs op aanvraag" {
-                        9999
-                    } else {
-                        price_raw
-                            // It starts with a euro sign and an NBSP,
-                            // so we use split_whitespace to handle that.
-                            .split_whitespace()
-                            .nth(1)
-                            .with_context(|| format!("invalid price {price_raw}"))?
-                            .replace(".", "")
-                            .parse()
-                            .with_context(|| format!("invalid price {price_raw}"))?
-                    };
-
-                    let title = house.select_one(&self.title_selector).context("no title")?;
-                    let uri = title.attr("href").context("no href in title")?;
-                    let url = format!("https://www.huurwoningen.nl{uri}");
-
-                    let area_raw = house
-                        .select_one_text(&self.area_selector)
-                        .context("no area")?;
-                    let area = area_raw
-                        .strip_suffix(" m²")
-                        .unwrap_or(area_raw)
+            let mut listings = parse_listings(houses, |house| {
+                let price_raw = house
+                    .select_one_text(&self.price_selector)
+                    .context("no price")?;
+
+                let price: usize = if price_raw == "Prijs op aanvraag" {
+                    PRICE_ON_REQUEST
+                } else {
+                    price_raw
+                        // It starts with a euro sign and an NBSP,
+                        // so we use split_whitespace to handle that.
+                        .split_whitespace()
+                        .nth(1)
+                        .with_context(|| format!("invalid price {price_raw}"))?
+                        .replace(".", "")
                         .parse()
-                        .with_context(|| format!("invalid area {area_raw}"))?;
-
-                    anyhow::Ok(ScrapeResult::Partial(PartialScrapeResult {
-                        title: "".to_string(),
-                        price,
-                        url,
-                        area,
-                    }))
-                })
-                .try_collect()?)
+                        .with_context(|| format!("invalid price {price_raw}"))?
+                };
+
+                let title = house.select_one(&self.title_selector).context("no title")?;
+                let uri = title.attr("href").context("no href in title")?;
+                let url = format!("https://www.huurwoningen.nl{uri}");
+
+                let area_raw = house
+                    .select_one_text(&self.area_selector)
+                    .context("no area")?;
+                let area = area_raw
+                    .strip_suffix(" m²")
+                    .unwrap_or(area_raw)
+                    .parse()
+                    .with_context(|| format!("invalid area {area_raw}"))?;
+
+                let address = title.text().next().unwrap_or_default().trim();
+                let postcode = house
+                    .select_one_text(&self.subtitle_selector)
+                    .ok()
+                    .and_then(parse_postcode);
+                let rooms = house
+                    .select_one_text(&self.rooms_selector)
+                    .ok()
+                    .and_then(leading_number);
+                let interior = house
+                    .select_one_text(&self.interior_selector)
+                    .ok()
+                    .and_then(|interior| interior.parse().ok());
+                let image_url = image_url(&house, &self.image_selector, &url);
+
+                anyhow::Ok(ScrapeResult::Partial(PartialScrapeResult {
+                    title: address.to_string(),
+                    price,
+                    url,
+                    area,
+                    rooms,
+                    interior,
+                    postcode,
+                    image_url,
+                    ..Default::default()
+                }))
+            });
+            listings.next_page = next_page_url(&document, &self.next_page_selector, &url);
+            Ok(listings)
         })
     }
 
     fn scrape_property(
         &self,
-        partial: PartialScrapeResult,
-    ) -> BoxFuture<anyhow::Result<FullScrapeResult>> {
+        mut partial: PartialScrapeResult,
+    ) -> BoxFuture<'_, anyhow::Result<FullScrapeResult>> {
         Box::pin(async {
-            let response = reqwest::get(&partial.url)
+            let response = self
+                .client
+                .get_text(&partial.url)
                 .await
-                .and_then(reqwest::Response::error_for_status)
-                .with_context(|| format!("failed to GET property at {}", partial.url))?
-                .text()
-                .await?;
+                .with_context(|| format!("failed to GET property at {}", partial.url))?;
 
             let appartment_document = Html::parse_document(&response);
             let map = appartment_document.select_one(&self.map_selector)?;
@@ -109,6 +168,23 @@ impl WebsiteScraper for HuurwoningenScraper {
                 .parse()
                 .context("invalid latitude")?;
 
+            partial.bedrooms = appartment_document
+                .select_one_text(&self.bedrooms_selector)
+                .ok()
+                .and_then(leading_number);
+            partial.deposit = appartment_document
+                .select_one_text(&self.deposit_selector)
+                .ok()
+                .and_then(parse_euros);
+            partial.energy_label = appartment_document
+                .select_one_text(&self.energy_label_selector)
+                .ok()
+                .map(|label| label.to_uppercase());
+            partial.available_from = appartment_document
+                .select_one_text(&self.available_selector)
+                .ok()
+                .and_then(Availability::parse_text);
+
             Ok(FullScrapeResult {
                 partial,
                 location: geo::Point::new(longitude, latitude),
@@ -116,3 +192,86 @@ impl WebsiteScraper for HuurwoningenScraper {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scraping::{
+        testing::{rotterdam, stand_in},
+        Interior,
+    };
+
+    const LISTINGS: &str = include_str!("../../tests/fixtures/huurwoningen/listings.html");
+    const PROPERTY: &str = include_str!("../../tests/fixtures/huurwoningen/property.html");
+
+    #[tokio::test]
+    async fn lists_properties() {
+        let client = stand_in(&[("/in/rotterdam/", LISTINGS)]).await;
+        let properties = HuurwoningenScraper::new(client, rotterdam())
+            .list_properties()
+            .await
+            .unwrap()
+            .properties;
+
+        assert_eq!(
+            properties,
+            vec![
+                ScrapeResult::Partial(PartialScrapeResult {
+                    title: "Nieuwe Binnenweg 201-A".to_string(),
+                    price: 1425,
+                    url: "https://www.huurwoningen.nl/huren/rotterdam/9c0d1e2f/nieuwe-binnenweg/"
+                        .to_string(),
+                    area: 64,
+                    rooms: Some(3),
+                    interior: Some(Interior::Furnished),
+                    postcode: Some("3021 GE".to_string()),
+                    // Lazy loaded, the placeholder is in `src`
+                    image_url: Some(
+                        "https://casco-media-prod.global.ssl.fastly.net/9c0d1e2f/nieuwe-binnenweg/400x300.jpg"
+                            .to_string()
+                    ),
+                    ..Default::default()
+                }),
+                ScrapeResult::Partial(PartialScrapeResult {
+                    title: "Schiedamseweg 88".to_string(),
+                    price: PRICE_ON_REQUEST,
+                    url: "https://www.huurwoningen.nl/huren/rotterdam/3a4b5c6d/schiedamseweg/"
+                        .to_string(),
+                    area: 48,
+                    ..Default::default()
+                }),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn scrapes_property() {
+        let client = stand_in(&[("/huren/rotterdam/9c0d1e2f/nieuwe-binnenweg/", PROPERTY)]).await;
+        let partial = PartialScrapeResult {
+            title: "Nieuwe Binnenweg 201-A".to_string(),
+            price: 1425,
+            url: "https://www.huurwoningen.nl/huren/rotterdam/9c0d1e2f/nieuwe-binnenweg/"
+                .to_string(),
+            area: 64,
+            ..Default::default()
+        };
+
+        let full = HuurwoningenScraper::new(client, rotterdam())
+            .scrape_property(partial.clone())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            full,
+            FullScrapeResult {
+                partial: PartialScrapeResult {
+                    bedrooms: Some(2),
+                    deposit: Some(2850),
+                    available_from: Some(Availability::Immediately),
+                    ..partial
+                },
+                location: geo::Point::new(4.4623, 51.9154),
+            }
+        );
+    }
+}
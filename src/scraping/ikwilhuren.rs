@@ -1,107 +1,155 @@
 use anyhow::Context;
 use futures::future::BoxFuture;
-use itertools::Itertools;
-use reqwest;
+use reqwest::Url;
 use scraper::{ElementRef, Html, Selector};
 
 use super::{
-    utils::SelectExt, FullScrapeResult, PartialScrapeResult, ScrapeResult, WebsiteScraper,
+    http::HttpClient,
+    utils::{
+        image_url, leading_number, next_page_url, parse_euros, parse_listings, place_name,
+        SelectExt,
+    },
+    FullScrapeResult, Listings, PartialScrapeResult, ScrapeResult, Search, WebsiteScraper,
 };
 
+/// Ikwilhuren searches around a place, rather than within the city limits.
+const SEARCH_RADIUS_KM: u32 = 5;
+
 pub struct IkwilhurenScraper {
+    client: HttpClient,
+    search: Search,
     listing_selector: Selector,
+    next_page_selector: Selector,
     title_selector: Selector,
     price_selector: Selector,
     area_selector: Selector,
     map_selector: Selector,
+    features_selector: Selector,
+    image_selector: Selector,
 }
 
-impl Default for IkwilhurenScraper {
-    fn default() -> Self {
+impl IkwilhurenScraper {
+    pub fn new(client: HttpClient, search: Search) -> Self {
         Self {
+            client,
+            search,
             listing_selector: Selector::parse(".card-woning").unwrap(),
+            next_page_selector: Selector::parse("a.page-link[rel=next]").unwrap(),
             title_selector: Selector::parse(".card-title a").unwrap(),
             price_selector: Selector::parse(".fw-bold").unwrap(),
             area_selector: Selector::parse("span:nth-child(2)").unwrap(),
             map_selector: Selector::parse("#maplibre-object").unwrap(),
+            features_selector: Selector::parse("span").unwrap(),
+            image_selector: Selector::parse("img.card-img-top").unwrap(),
         }
     }
 }
 
 impl WebsiteScraper for IkwilhurenScraper {
-    fn list_properties(&self) -> BoxFuture<anyhow::Result<Vec<ScrapeResult>>> {
-        Box::pin(async {
-            let response = reqwest::get("https://ikwilhuren.nu/aanbod/?sort=aanbodDESC")
-                .await?
-                .error_for_status()?
-                .text()
-                .await?;
+    fn list_properties(&self) -> BoxFuture<'_, anyhow::Result<Listings>> {
+        // Without a place it lists properties from all over the Netherlands
+        let radius = SEARCH_RADIUS_KM.to_string();
+        let mut query = vec![
+            ("plaats", place_name(&self.search.city)),
+            ("straal", radius),
+        ];
+        if let Some(min_price) = self.search.min_price {
+            query.push(("prijsvan", min_price.to_string()));
+        }
+        if let Some(max_price) = self.search.max_price {
+            query.push(("prijstot", max_price.to_string()));
+        }
+        query.push(("sort", "aanbodDESC".to_string()));
+
+        let url = Url::parse_with_params("https://ikwilhuren.nu/aanbod/", &query)
+            .expect("the base URL is valid");
+        self.list_page(url.to_string())
+    }
+
+    fn list_page(&self, url: String) -> BoxFuture<'_, anyhow::Result<Listings>> {
+        Box::pin(async move {
+            let response = self.client.get_text(&url).await?;
 
             let document = Html::parse_document(&response);
             let listings = document.select(&self.listing_selector);
 
-            let results = listings
-                .map(|listing| {
-                    let title_element = listing.select_one(&self.title_selector)?;
-                    let url = format!(
-                        "https://ikwilhuren.nu{}",
-                        title_element.attr("href").context("missing URL")?
-                    );
-
-                    let title = title_element
-                        .text()
-                        .next()
-                        .context("no title")?
-                        .trim()
-                        .to_string();
-
-                    let price_element = listing.select_one(&self.price_selector)?;
-                    let raw_price = price_element.text().next().context("no price")?;
-                    let price = raw_price
-                        .split(" ")
-                        .nth(1)
-                        .unwrap()
-                        .replace(".", "")
-                        .replace(",", "")
-                        .replace("-", "")
-                        .parse()
-                        .with_context(|| format!("Invalid price format: {raw_price}"))?;
-
-                    let price_parent =
-                        ElementRef::wrap(price_element.parent().context("no parent of price")?)
-                            .unwrap();
-
-                    let area = price_parent.select_one_text(&self.area_selector)?;
-                    let area = area
-                        .split(" ")
-                        .next()
-                        .unwrap_or(area)
-                        .parse()
-                        .with_context(|| format!("invalid area: {area}"))?;
-
-                    anyhow::Ok(ScrapeResult::Partial(PartialScrapeResult {
-                        title,
-                        price,
-                        url,
-                        area,
-                    }))
-                })
-                .try_collect()?;
+            let mut listings = parse_listings(listings, |listing| {
+                let title_element = listing.select_one(&self.title_selector)?;
+                let url = format!(
+                    "https://ikwilhuren.nu{}",
+                    title_element.attr("href").context("missing URL")?
+                );
+
+                let title = title_element
+                    .text()
+                    .next()
+                    .context("no title")?
+                    .trim()
+                    .to_string();
+
+                let price_element = listing.select_one(&self.price_selector)?;
+                let raw_price = listing.select_one_text(&self.price_selector)?;
+                // e.g. "€ 1.595,- p/m"
+                let price = parse_euros(raw_price)
+                    .with_context(|| format!("invalid price: {raw_price}"))?;
+
+                let price_parent = price_element
+                    .parent()
+                    .and_then(ElementRef::wrap)
+                    .context("no parent of price")?;
 
-            Ok(results)
+                let area = price_parent.select_one_text(&self.area_selector)?;
+                let area = area
+                    .split(" ")
+                    .next()
+                    .unwrap_or(area)
+                    .parse()
+                    .with_context(|| format!("invalid area: {area}"))?;
+
+                // e.g. "3 kamers", next to the area
+                let rooms = price_parent
+                    .select(&self.features_selector)
+                    .filter_map(|feature| feature.text().next())
+                    .find(|feature| feature.contains("kamer"))
+                    .and_then(leading_number);
+                let image_url = image_url(&listing, &self.image_selector, &url);
+
+                let partial = PartialScrapeResult {
+                    title,
+                    price,
+                    url,
+                    area,
+                    rooms,
+                    image_url,
+                    ..Default::default()
+                };
+                // Cards shown on the map have coordinates,
+                // the others have them on the property page.
+                let location = listing
+                    .attr("data-lat")
+                    .zip(listing.attr("data-lng"))
+                    .and_then(|(latitude, longitude)| {
+                        Some(geo::Point::new(
+                            longitude.parse().ok()?,
+                            latitude.parse().ok()?,
+                        ))
+                    });
+                anyhow::Ok(match location {
+                    Some(location) => ScrapeResult::Full(FullScrapeResult { partial, location }),
+                    None => ScrapeResult::Partial(partial),
+                })
+            });
+            listings.next_page = next_page_url(&document, &self.next_page_selector, &url);
+            Ok(listings)
         })
     }
 
     fn scrape_property(
         &self,
         partial: PartialScrapeResult,
-    ) -> BoxFuture<anyhow::Result<FullScrapeResult>> {
+    ) -> BoxFuture<'_, anyhow::Result<FullScrapeResult>> {
         Box::pin(async move {
-            let response = reqwest::get(&partial.url)
-                .await?
-                .error_for_status()?
-                .text()
-                .await?;
+            let response = self.client.get_text(&partial.url).await?;
 
             let document = Html::parse_document(&response);
             let map = document.select_one(&self.map_selector)?;
@@ -123,3 +171,106 @@ impl WebsiteScraper for IkwilhurenScraper {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scraping::testing::{rotterdam, stand_in};
+
+    const LISTINGS: &str = include_str!("../../tests/fixtures/ikwilhuren/listings.html");
+    const PROPERTY: &str = include_str!("../../tests/fixtures/ikwilhuren/property.html");
+
+    #[tokio::test]
+    async fn lists_properties() {
+        let client = stand_in(&[(
+            "/aanbod/?plaats=Rotterdam&straal=5&sort=aanbodDESC",
+            LISTINGS,
+        )])
+        .await;
+        let listings = IkwilhurenScraper::new(client, rotterdam())
+            .list_properties()
+            .await
+            .unwrap();
+
+        assert_eq!(
+            listings.properties,
+            vec![
+                ScrapeResult::Full(FullScrapeResult {
+                    partial: PartialScrapeResult {
+                        title: "Mathenesserlaan 300".to_string(),
+                        price: 1595,
+                        url: "https://ikwilhuren.nu/object/rotterdam-mathenesserlaan-300/"
+                            .to_string(),
+                        area: 82,
+                        rooms: Some(4),
+                        image_url: Some(
+                            "https://ikwilhuren.nu/media/objects/rotterdam-mathenesserlaan-300.jpg"
+                                .to_string()
+                        ),
+                        ..Default::default()
+                    },
+                    location: geo::Point::new(4.4650, 51.9112),
+                }),
+                ScrapeResult::Partial(PartialScrapeResult {
+                    title: "Broersvest 12".to_string(),
+                    price: 1150,
+                    url: "https://ikwilhuren.nu/object/schiedam-broersvest-12/".to_string(),
+                    area: 51,
+                    image_url: Some(
+                        "https://ikwilhuren.nu/media/objects/schiedam-broersvest-12.jpg"
+                            .to_string()
+                    ),
+                    ..Default::default()
+                }),
+            ]
+        );
+        // A price we can't make sense of is an error for that listing, not a panic
+        assert_eq!(listings.errors.len(), 1);
+        assert!(listings.errors[0].html.contains("Lijnbaan 90"));
+    }
+
+    #[tokio::test]
+    async fn searches_place_and_price_range() {
+        let client = stand_in(&[(
+            "/aanbod/?plaats=Den+Haag&straal=5&prijstot=1500&sort=aanbodDESC",
+            LISTINGS,
+        )])
+        .await;
+        let search = Search {
+            city: "den-haag".to_string(),
+            min_price: None,
+            max_price: Some(1500),
+        };
+
+        let listings = IkwilhurenScraper::new(client, search)
+            .list_properties()
+            .await
+            .unwrap();
+        assert_eq!(listings.properties.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn scrapes_property() {
+        let client = stand_in(&[("/object/rotterdam-mathenesserlaan-300/", PROPERTY)]).await;
+        let partial = PartialScrapeResult {
+            title: "Mathenesserlaan 300".to_string(),
+            price: 1595,
+            url: "https://ikwilhuren.nu/object/rotterdam-mathenesserlaan-300/".to_string(),
+            area: 82,
+            ..Default::default()
+        };
+
+        let full = IkwilhurenScraper::new(client, rotterdam())
+            .scrape_property(partial.clone())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            full,
+            FullScrapeResult {
+                partial,
+                location: geo::Point::new(4.4650, 51.9112),
+            }
+        );
+    }
+}
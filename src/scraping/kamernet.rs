@@ -0,0 +1,246 @@
+use anyhow::Context;
+use futures::future::BoxFuture;
+use scraper::{Html, Selector};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{
+    http::HttpClient,
+    utils::{parse_postcode, place_name, SelectExt},
+    Availability, FullScrapeResult, ListingError, Listings, PartialScrapeResult, ScrapeResult,
+    Search, WebsiteScraper,
+};
+
+/// Kamernet searches around a place, rather than within the city limits.
+const SEARCH_RADIUS_KM: u32 = 5;
+/// The most listings the search returns at once.
+const PAGE_SIZE: u32 = 30;
+
+/// Kamernet mostly lists rooms and studios, but has apartments as well.
+pub struct KamernetScraper {
+    client: HttpClient,
+    search: Search,
+    latitude_selector: Selector,
+    longitude_selector: Selector,
+}
+
+impl KamernetScraper {
+    pub fn new(client: HttpClient, search: Search) -> Self {
+        Self {
+            client,
+            search,
+            latitude_selector: Selector::parse("meta[property='place:location:latitude']").unwrap(),
+            longitude_selector: Selector::parse("meta[property='place:location:longitude']")
+                .unwrap(),
+        }
+    }
+}
+
+impl WebsiteScraper for KamernetScraper {
+    fn list_properties(&self) -> BoxFuture<'_, anyhow::Result<Listings>> {
+        Box::pin(async {
+            let request = SearchRequest {
+                location: place_name(&self.search.city),
+                radius_km: SEARCH_RADIUS_KM,
+                min_rent: self.search.min_price,
+                max_rent: self.search.max_price,
+                page_no: 1,
+                size: PAGE_SIZE,
+                // Newest first
+                sort: 1,
+            };
+            let response: SearchResponse = self
+                .client
+                .post_json(
+                    "https://kamernet.nl/services/api/listing/findlistings",
+                    &request,
+                )
+                .await?;
+
+            let mut listings = Listings::default();
+            for item in response.listings.iter() {
+                match Listing::deserialize(item) {
+                    Ok(listing) => listings.properties.push(listing.into_property()),
+                    Err(error) => listings.errors.push(ListingError {
+                        html: item.to_string(),
+                        error: error.into(),
+                    }),
+                }
+            }
+            Ok(listings)
+        })
+    }
+
+    fn scrape_property(
+        &self,
+        partial: PartialScrapeResult,
+    ) -> BoxFuture<'_, anyhow::Result<FullScrapeResult>> {
+        Box::pin(async move {
+            let response = self
+                .client
+                .get_text(&partial.url)
+                .await
+                .with_context(|| format!("failed to GET property at {}", partial.url))?;
+
+            let document = Html::parse_document(&response);
+            let latitude: f64 = document
+                .select_one(&self.latitude_selector)?
+                .attr("content")
+                .context("no latitude")?
+                .parse()
+                .context("invalid latitude")?;
+            let longitude: f64 = document
+                .select_one(&self.longitude_selector)?
+                .attr("content")
+                .context("no longitude")?
+                .parse()
+                .context("invalid longitude")?;
+
+            Ok(FullScrapeResult {
+                partial,
+                location: geo::Point::new(longitude, latitude),
+            })
+        })
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SearchRequest {
+    location: String,
+    radius_km: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    min_rent: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_rent: Option<usize>,
+    page_no: u32,
+    size: u32,
+    sort: u32,
+}
+
+#[derive(Deserialize)]
+struct SearchResponse {
+    /// Parsed one by one, so a single odd listing doesn't break the others.
+    listings: Vec<Value>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Listing {
+    street: String,
+    /// Including utilities, which rooms usually are.
+    #[serde(rename = "totalRentalPrice")]
+    price: usize,
+    #[serde(rename = "surfaceArea")]
+    area: u32,
+    #[serde(rename = "detailedUrl")]
+    url: String,
+
+    postal_code: Option<String>,
+    furnishing: Option<String>,
+    availability_start_date: Option<String>,
+    thumbnail_url: Option<String>,
+}
+
+impl Listing {
+    fn into_property(self) -> ScrapeResult {
+        ScrapeResult::Partial(PartialScrapeResult {
+            title: self.street,
+            price: self.price,
+            url: format!("https://kamernet.nl{}", self.url),
+            area: self.area,
+            interior: self.furnishing.and_then(|interior| interior.parse().ok()),
+            // e.g. "2025-04-01T00:00:00"
+            available_from: self
+                .availability_start_date
+                .as_deref()
+                .and_then(|date| date.get(..10))
+                .and_then(Availability::parse_text),
+            postcode: self.postal_code.as_deref().and_then(parse_postcode),
+            image_url: self.thumbnail_url,
+            ..Default::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::scraping::{
+        testing::{rotterdam, stand_in},
+        Interior,
+    };
+
+    const SEARCH: &str = include_str!("../../tests/fixtures/kamernet/search.json");
+    const PROPERTY: &str = include_str!("../../tests/fixtures/kamernet/property.html");
+
+    #[tokio::test]
+    async fn lists_properties() {
+        let client = stand_in(&[("/services/api/listing/findlistings", SEARCH)]).await;
+        let listings = KamernetScraper::new(client, rotterdam())
+            .list_properties()
+            .await
+            .unwrap();
+
+        // The room without a surface area is reported, the others are still listed
+        assert_eq!(listings.errors.len(), 1);
+        assert!(listings.errors[0].html.contains("Beijerlandselaan"));
+        assert_eq!(
+            listings.properties,
+            vec![
+                ScrapeResult::Partial(PartialScrapeResult {
+                    title: "Zwart Janstraat".to_string(),
+                    price: 725,
+                    url: "https://kamernet.nl/en/for-rent/room-rotterdam/zwart-janstraat/room-2304512"
+                        .to_string(),
+                    area: 16,
+                    interior: Some(Interior::Furnished),
+                    available_from: Some(Availability::From(
+                        NaiveDate::from_ymd_opt(2025, 4, 1).unwrap()
+                    )),
+                    postcode: Some("3035 AN".to_string()),
+                    image_url: Some(
+                        "https://resources.kamernet.nl/image/7d1c33ab-35b2-4e2a-8c9e-5a0c2d1f7e10/resize/800-600"
+                            .to_string()
+                    ),
+                    ..Default::default()
+                }),
+                ScrapeResult::Partial(PartialScrapeResult {
+                    title: "Nieuwe Binnenweg".to_string(),
+                    price: 1395,
+                    url: "https://kamernet.nl/en/for-rent/apartment-rotterdam/nieuwe-binnenweg/apartment-2304498"
+                        .to_string(),
+                    area: 48,
+                    interior: Some(Interior::Upholstered),
+                    postcode: Some("3014 GH".to_string()),
+                    ..Default::default()
+                }),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn scrapes_coordinates_from_property_page() {
+        let client = stand_in(&[(
+            "/en/for-rent/room-rotterdam/zwart-janstraat/room-2304512",
+            PROPERTY,
+        )])
+        .await;
+        let partial = PartialScrapeResult {
+            title: "Zwart Janstraat".to_string(),
+            price: 725,
+            url: "https://kamernet.nl/en/for-rent/room-rotterdam/zwart-janstraat/room-2304512"
+                .to_string(),
+            area: 16,
+            ..Default::default()
+        };
+
+        let full = KamernetScraper::new(client, rotterdam())
+            .scrape_property(partial)
+            .await
+            .unwrap();
+        assert_eq!(full.location, geo::Point::new(4.4834, 51.9287));
+    }
+}
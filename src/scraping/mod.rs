@@ -1,27 +1,294 @@
+pub mod funda;
+pub mod http;
+pub mod huurstunt;
 pub mod huurwoningen;
 pub mod ikwilhuren;
+pub mod kamernet;
 pub mod pararius;
+pub mod realtimelistings;
 pub mod rotterdamwonen;
-pub mod verra;
+pub mod selector;
 pub mod vesteda;
+pub mod woonnetrijnmond;
+#[cfg(test)]
+mod testing;
 mod utils;
 
-use std::ops::Deref;
+use std::{fmt, ops::Deref, str::FromStr};
 
+use anyhow::Context;
+use chrono::NaiveDate;
 use futures::future::BoxFuture;
+use serde::Deserialize;
 
-pub trait WebsiteScraper {
+use self::{
+    funda::FundaScraper,
+    http::HttpClient,
+    huurstunt::HuurstuntScraper,
+    huurwoningen::HuurwoningenScraper,
+    ikwilhuren::IkwilhurenScraper,
+    kamernet::KamernetScraper,
+    pararius::ParariusScraper,
+    realtimelistings::{RealtimeListingsDefinition, RealtimeListingsScraper},
+    rotterdamwonen::RotterdamWonenScraper,
+    selector::{SelectorDefinition, SelectorScraper},
+    vesteda::VestedaScraper,
+    woonnetrijnmond::WoonnetRijnmondScraper,
+};
+
+/// A website to scrape, either with a scraper of its own or with a definition.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Website {
+    Pararius,
+    Huurwoningen,
+    Ikwilhuren,
+    RotterdamWonen,
+    Verra,
+    Vesteda,
+    Funda,
+    Kamernet,
+    Huurstunt,
+    WoonnetRijnmond,
+    /// A website described by a definition, by its name.
+    Defined(String),
+}
+
+impl Website {
+    /// The websites with a scraper of their own.
+    pub const BUILT_IN: [Website; 10] = [
+        Website::Pararius,
+        Website::Huurwoningen,
+        Website::Ikwilhuren,
+        Website::RotterdamWonen,
+        Website::Verra,
+        Website::Vesteda,
+        Website::Funda,
+        Website::Kamernet,
+        Website::Huurstunt,
+        Website::WoonnetRijnmond,
+    ];
+
+    /// The name used in the configuration and notifications, e.g. "rotterdam-wonen".
+    pub fn name(&self) -> &str {
+        match self {
+            Website::Pararius => "pararius",
+            Website::Huurwoningen => "huurwoningen",
+            Website::Ikwilhuren => "ikwilhuren",
+            Website::RotterdamWonen => "rotterdam-wonen",
+            Website::Verra => "verra",
+            Website::Vesteda => "vesteda",
+            Website::Funda => "funda",
+            Website::Kamernet => "kamernet",
+            Website::Huurstunt => "huurstunt",
+            Website::WoonnetRijnmond => "woonnet-rijnmond",
+            Website::Defined(name) => name,
+        }
+    }
+
+    /// Names of websites without a scraper of their own are defined websites.
+    pub fn from_name(name: &str) -> Website {
+        Website::BUILT_IN
+            .into_iter()
+            .find(|website| website.name() == name)
+            .unwrap_or_else(|| Website::Defined(name.to_string()))
+    }
+
+    /// Defined websites are scraped using their definition in `definitions`.
+    pub fn scraper(
+        &self,
+        client: HttpClient,
+        search: &Search,
+        definitions: &[Definition],
+    ) -> anyhow::Result<Box<dyn WebsiteScraper>> {
+        let search = search.clone();
+        Ok(match self {
+            Website::Pararius => Box::new(ParariusScraper::new(client, search)),
+            Website::Huurwoningen => Box::new(HuurwoningenScraper::new(client, search)),
+            Website::Ikwilhuren => Box::new(IkwilhurenScraper::new(client, search)),
+            Website::RotterdamWonen => Box::new(RotterdamWonenScraper::new(client)),
+            Website::Verra => Box::new(RealtimeListingsScraper::new(
+                client,
+                RealtimeListingsDefinition::verra(),
+            )),
+            Website::Vesteda => Box::new(VestedaScraper::new(client, search)),
+            Website::Funda => Box::new(FundaScraper::new(client, search)),
+            Website::Kamernet => Box::new(KamernetScraper::new(client, search)),
+            Website::Huurstunt => Box::new(HuurstuntScraper::new(client, search)),
+            Website::WoonnetRijnmond => Box::new(WoonnetRijnmondScraper::new(client, search)),
+            Website::Defined(name) => {
+                let definition = definitions
+                    .iter()
+                    .find(|definition| definition.name() == name)
+                    .with_context(|| format!("no definition of website {name}"))?;
+                match definition {
+                    Definition::Selector(definition) => {
+                        Box::new(SelectorScraper::new(client, search, *definition.clone()))
+                    }
+                    Definition::RealtimeListings(definition) => {
+                        Box::new(RealtimeListingsScraper::new(client, *definition.clone()))
+                    }
+                }
+            }
+        })
+    }
+
+    /// Whether the website has properties in `city`.
+    /// Local websites only have one city or region, the others can search any city.
+    pub(crate) fn covers(&self, city: &str) -> bool {
+        match self {
+            Website::RotterdamWonen | Website::Verra => city == "rotterdam",
+            Website::WoonnetRijnmond => woonnetrijnmond::REGION.contains(&city),
+            _ => true,
+        }
+    }
+}
+
+/// How to scrape a website without a scraper of its own.
+#[derive(Debug, Clone)]
+pub enum Definition {
+    /// From a definition file.
+    Selector(Box<SelectorDefinition>),
+    /// A broker from the configuration.
+    RealtimeListings(Box<RealtimeListingsDefinition>),
+}
+
+impl Definition {
+    pub fn name(&self) -> &str {
+        match self {
+            Definition::Selector(definition) => definition.name(),
+            Definition::RealtimeListings(definition) => definition.name(),
+        }
+    }
+
+    /// Whether the website has properties in `city`, like `Website::covers`.
+    pub(crate) fn covers(&self, city: &str) -> bool {
+        match self {
+            Definition::Selector(_) => true,
+            Definition::RealtimeListings(definition) => definition.city() == city,
+        }
+    }
+}
+
+impl FromStr for Website {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Website::from_name(s))
+    }
+}
+
+impl<'de> Deserialize<'de> for Website {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Ok(Website::from_name(&name))
+    }
+}
+
+impl fmt::Display for Website {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// What to search for on a website.
+#[derive(Debug, Clone, Default)]
+pub struct Search {
+    /// The city as used in URLs, e.g. "den-haag".
+    pub city: String,
+    /// Websites which don't support searching by price list everything,
+    /// subscriber filters still apply.
+    pub min_price: Option<usize>,
+    pub max_price: Option<usize>,
+}
+
+impl Search {
+    /// The price range in the "1000-1500" format used by several websites.
+    pub(super) fn price_range(&self) -> Option<String> {
+        if self.min_price.is_none() && self.max_price.is_none() {
+            return None;
+        }
+        let (min_price, max_price) = self.price_bounds();
+        let max_price = max_price.map(|max| max.to_string());
+        Some(format!("{min_price}-{}", max_price.unwrap_or_default()))
+    }
+
+    /// The lowest and highest price to search for, for websites which want them separately.
+    pub(super) fn price_bounds(&self) -> (usize, Option<usize>) {
+        (self.min_price.unwrap_or(0), self.max_price)
+    }
+}
+
+pub trait WebsiteScraper: Send + Sync {
     /// List the most recent properties on the website.
-    /// Return their links.
-    fn list_properties(&self) -> BoxFuture<anyhow::Result<Vec<ScrapeResult>>>;
+    /// Listings which couldn't be parsed are returned as errors, without failing the others.
+    fn list_properties(&self) -> BoxFuture<'_, anyhow::Result<Listings>>;
+
+    /// List the properties on a next page, linked from a previous page of listings.
+    /// Only websites which return a `next_page` link need to support this.
+    fn list_page(&self, _url: String) -> BoxFuture<'_, anyhow::Result<Listings>> {
+        Box::pin(futures::future::ready(Err(anyhow::anyhow!(
+            "pagination is not supported"
+        ))))
+    }
+
+    /// List the most recent properties, following the links to the next pages until a page
+    /// has a property for which `is_known` is true, or `max_pages` pages were listed.
+    fn list_new_properties<'a>(
+        &'a self,
+        is_known: &'a (dyn Fn(&str) -> bool + Sync),
+        max_pages: u32,
+    ) -> BoxFuture<'a, anyhow::Result<Listings>> {
+        Box::pin(async move {
+            let mut listings = self.list_properties().await?;
+            // Where the properties of the last listed page start
+            let mut page_start = 0;
+            let mut pages = 1;
+
+            while let Some(next_page) = listings.next_page.take() {
+                let page = &listings.properties[page_start..];
+                if pages >= max_pages || page.iter().any(|property| is_known(&property.url)) {
+                    break;
+                }
+
+                // The pages we already have are still useful
+                let next = match self.list_page(next_page.clone()).await {
+                    Ok(next) => next,
+                    Err(e) => {
+                        tracing::warn!("Failed to list next page {next_page}: {e:?}");
+                        break;
+                    }
+                };
+                page_start = listings.properties.len();
+                pages += 1;
+
+                // Properties listed in the meantime push others to the next page,
+                // so we may see them twice.
+                let unseen: Vec<_> = next
+                    .properties
+                    .into_iter()
+                    .filter(|property| {
+                        !listings
+                            .properties
+                            .iter()
+                            .any(|listed| listed.url == property.url)
+                    })
+                    .collect();
+                listings.properties.extend(unseen);
+                listings.errors.extend(next.errors);
+                listings.next_page = next.next_page;
+            }
+
+            Ok(listings)
+        })
+    }
 
     /// Scrape a given property
     fn scrape_property(
         &self,
         partial: PartialScrapeResult,
-    ) -> BoxFuture<anyhow::Result<FullScrapeResult>>;
+    ) -> BoxFuture<'_, anyhow::Result<FullScrapeResult>>;
 
-    fn full(&self, result: ScrapeResult) -> BoxFuture<anyhow::Result<FullScrapeResult>> {
+    fn full(&self, result: ScrapeResult) -> BoxFuture<'_, anyhow::Result<FullScrapeResult>> {
         match result {
             ScrapeResult::Partial(partial) => self.scrape_property(partial),
             ScrapeResult::Full(full) => Box::pin(futures::future::ready(Ok(full))),
@@ -29,22 +296,155 @@ pub trait WebsiteScraper {
     }
 }
 
-#[derive(Debug, Clone)]
+/// The properties on a listing page.
+#[derive(Debug, Default)]
+pub struct Listings {
+    pub(super) properties: Vec<ScrapeResult>,
+    /// Listings which couldn't be parsed, usually because the website changed its layout.
+    pub(super) errors: Vec<ListingError>,
+    /// The link to the next page of listings, if any.
+    pub(super) next_page: Option<String>,
+}
+
+impl Listings {
+    pub fn properties(&self) -> &[ScrapeResult] {
+        &self.properties
+    }
+}
+
+impl From<Vec<ScrapeResult>> for Listings {
+    fn from(properties: Vec<ScrapeResult>) -> Self {
+        Self {
+            properties,
+            errors: Vec::new(),
+            next_page: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ListingError {
+    /// The HTML of the listing, to see what changed.
+    pub(super) html: String,
+    pub(super) error: anyhow::Error,
+}
+
+/// The price of listings with an unknown price, e.g. "Price on request". It's above any rent,
+/// so they're only sent to subscribers without a maximum price, but it's never shown as a price.
+pub const PRICE_ON_REQUEST: usize = 9999;
+
+/// Everything except the title, price, URL and area is optional,
+/// as not every website lists it (or only lists it on the property page).
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct PartialScrapeResult {
-    #[allow(unused)]
-    title: String,
+    pub(super) title: String,
     pub(super) price: usize,
     pub(super) url: String,
     pub(super) area: u32,
+    pub(super) rooms: Option<u32>,
+    pub(super) bedrooms: Option<u32>,
+    pub(super) interior: Option<Interior>,
+    pub(super) energy_label: Option<String>,
+    pub(super) available_from: Option<Availability>,
+    pub(super) deposit: Option<usize>,
+    /// Normalized to the "1234 AB" format.
+    pub(super) postcode: Option<String>,
+    /// The first photo of the property.
+    pub(super) image_url: Option<String>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct FullScrapeResult {
-    partial: PartialScrapeResult,
+    pub(super) partial: PartialScrapeResult,
     pub(super) location: geo::Point<f64>,
 }
 
-#[derive(Clone)]
+impl Deref for FullScrapeResult {
+    type Target = PartialScrapeResult;
+
+    fn deref(&self) -> &Self::Target {
+        &self.partial
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Interior {
+    Furnished,
+    Upholstered,
+    Shell,
+}
+
+impl Interior {
+    pub(super) fn as_str(&self) -> &'static str {
+        match self {
+            Interior::Furnished => "furnished",
+            Interior::Upholstered => "upholstered",
+            Interior::Shell => "shell",
+        }
+    }
+}
+
+impl FromStr for Interior {
+    type Err = anyhow::Error;
+
+    /// Accepts both the English and Dutch terms used by the websites.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+        // Check "unfurnished" before "furnished", as the latter is a substring of the former.
+        if s.contains("upholstered") || s.contains("gestoffeerd") {
+            Ok(Interior::Upholstered)
+        } else if ["shell", "kaal", "unfurnished", "ongemeubileerd"]
+            .iter()
+            .any(|term| s.contains(term))
+        {
+            Ok(Interior::Shell)
+        } else if s.contains("furnished") || s.contains("gemeubileerd") {
+            Ok(Interior::Furnished)
+        } else {
+            anyhow::bail!("unknown interior: {s}")
+        }
+    }
+}
+
+impl fmt::Display for Interior {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Availability {
+    Immediately,
+    From(NaiveDate),
+}
+
+impl Availability {
+    /// Parse texts like "Immediately", "Per direct" or "From 01-03-2025".
+    pub(super) fn parse_text(text: &str) -> Option<Self> {
+        let lowercase = text.to_lowercase();
+        if lowercase.contains("immediately") || lowercase.contains("direct") {
+            return Some(Availability::Immediately);
+        }
+
+        text.split_whitespace().find_map(|word| {
+            NaiveDate::parse_from_str(word, "%d-%m-%Y")
+                .or_else(|_| NaiveDate::parse_from_str(word, "%Y-%m-%d"))
+                .ok()
+                .map(Availability::From)
+        })
+    }
+}
+
+impl fmt::Display for Availability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Availability::Immediately => f.write_str("immediately"),
+            Availability::From(date) => write!(f, "{}", date.format("%Y-%m-%d")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum ScrapeResult {
     Partial(PartialScrapeResult),
     Full(FullScrapeResult),
@@ -60,3 +460,25 @@ impl Deref for ScrapeResult {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_interior() {
+        for (text, interior) in [
+            ("Furnished", Interior::Furnished),
+            ("Gemeubileerd", Interior::Furnished),
+            ("Upholstered", Interior::Upholstered),
+            ("gestoffeerd", Interior::Upholstered),
+            ("Unfurnished", Interior::Shell),
+            ("Ongemeubileerd", Interior::Shell),
+            (" Kaal ", Interior::Shell),
+            ("shell", Interior::Shell),
+        ] {
+            assert_eq!(text.parse::<Interior>().unwrap(), interior, "{text}");
+        }
+        assert!("Parking".parse::<Interior>().is_err());
+    }
+}
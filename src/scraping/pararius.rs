@@ -1,27 +1,45 @@
 use anyhow::Context;
 use futures::future::BoxFuture;
-use itertools::Itertools;
 use scraper::{Html, Selector};
 
 use super::{
-    utils::SelectExt, FullScrapeResult, PartialScrapeResult, ScrapeResult, WebsiteScraper,
+    http::HttpClient,
+    utils::{
+        image_url, leading_number, next_page_url, parse_euros, parse_listings, parse_postcode,
+        SelectExt,
+    },
+    Availability, FullScrapeResult, Listings, PartialScrapeResult, ScrapeResult, Search,
+    WebsiteScraper, PRICE_ON_REQUEST,
 };
 
 pub struct ParariusScraper {
+    client: HttpClient,
+    search: Search,
     // Unfortunately `scraper` doesn't have a compile-time checked way to define selectors.
     houses_selector: Selector,
+    next_page_selector: Selector,
     title_selector: Selector,
-    #[allow(unused)]
     subtitle_selector: Selector,
     map_selector: Selector,
     price_selector: Selector,
     area_selector: Selector,
+    rooms_selector: Selector,
+    interior_selector: Selector,
+    image_selector: Selector,
+    // These are only on the property page
+    bedrooms_selector: Selector,
+    deposit_selector: Selector,
+    energy_label_selector: Selector,
+    available_selector: Selector,
 }
 
-impl Default for ParariusScraper {
-    fn default() -> Self {
+impl ParariusScraper {
+    pub fn new(client: HttpClient, search: Search) -> Self {
         Self {
+            client,
+            search,
             houses_selector: Selector::parse("section.listing-search-item--for-rent").unwrap(),
+            next_page_selector: Selector::parse("a.pagination__link--next").unwrap(),
             title_selector: Selector::parse("a.listing-search-item__link--title").unwrap(),
             // subtitle class has a ' at the end, but it's an invalid CSS class name, so we use the ^=
             // operator to match the start of the class name
@@ -30,86 +48,117 @@ impl Default for ParariusScraper {
             map_selector: Selector::parse("wc-detail-map").unwrap(),
             price_selector: Selector::parse("div.listing-search-item__price").unwrap(),
             area_selector: Selector::parse(".illustrated-features__item--surface-area").unwrap(),
+            rooms_selector: Selector::parse(".illustrated-features__item--number-of-rooms")
+                .unwrap(),
+            interior_selector: Selector::parse(".illustrated-features__item--interior").unwrap(),
+            image_selector: Selector::parse("img.picture__image").unwrap(),
+            bedrooms_selector: Selector::parse(
+                "dd.listing-features__description--number_of_bedrooms",
+            )
+            .unwrap(),
+            deposit_selector: Selector::parse("dd.listing-features__description--deposit").unwrap(),
+            energy_label_selector: Selector::parse(
+                "dd.listing-features__description--energy_label",
+            )
+            .unwrap(),
+            available_selector: Selector::parse("dd.listing-features__description--available")
+                .unwrap(),
         }
     }
 }
 
 impl WebsiteScraper for ParariusScraper {
-    fn list_properties(&self) -> BoxFuture<anyhow::Result<Vec<ScrapeResult>>> {
-        Box::pin(async {
-            // By default querying this URL returns results sorted by newest first
-            let response = reqwest::get("https://www.pararius.com/apartments/rotterdam")
-                .await?
-                .error_for_status()?
-                .text()
-                .await?;
+    fn list_properties(&self) -> BoxFuture<'_, anyhow::Result<Listings>> {
+        // By default querying this URL returns results sorted by newest first
+        let mut url = format!("https://www.pararius.com/apartments/{}", self.search.city);
+        if let Some(price_range) = self.search.price_range() {
+            url.push_str(&format!("/{price_range}"));
+        }
+        self.list_page(url)
+    }
+
+    fn list_page(&self, url: String) -> BoxFuture<'_, anyhow::Result<Listings>> {
+        Box::pin(async move {
+            let response = self.client.get_text(&url).await?;
 
             let document = Html::parse_document(&response);
             let houses = document.select(&self.houses_selector);
 
-            Ok(houses
-                .into_iter()
-                .map(|house| {
-                    let title = house.select_one(&self.title_selector)?;
-                    let raw_address = title.text().next().context("no address")?.trim();
-                    let address = raw_address
-                        .split_once(" ")
-                        .map(|(_, rest)| rest)
-                        .unwrap_or(raw_address);
-
-                    //let zipcode = house.select_one_text(&self.subtitle_selector)?
-                    //    // "1234 AB" -> 7
-                    //    .split_at_checked(7)
-                    //    .context("invalid zipcode")?
-                    //    .0;
-
-                    let uri = title.attr("href").context("no link")?;
-                    let url = format!("https://pararius.com{}", uri);
-
-                    let raw_price = house.select_one_text(&self.price_selector)?;
-                    let price: usize = if raw_price == "Price on request" {
-                        9999
-                    } else {
-                        raw_price
-                            .split(" ")
-                            .next()
-                            .unwrap()
-                            .replace("€", "")
-                            .replace(",", "")
-                            .parse()
-                            .with_context(|| format!("invalid price: {raw_price}"))?
-                    };
-
-                    let area = house.select_one_text(&self.area_selector)?;
-                    let area = area
+            let mut listings = parse_listings(houses, |house| {
+                let title = house.select_one(&self.title_selector)?;
+                let raw_address = title.text().next().context("no address")?.trim();
+                let address = raw_address
+                    .split_once(" ")
+                    .map(|(_, rest)| rest)
+                    .unwrap_or(raw_address);
+
+                let postcode = house
+                    .select_one_text(&self.subtitle_selector)
+                    .ok()
+                    .and_then(parse_postcode);
+
+                let uri = title.attr("href").context("no link")?;
+                let url = format!("https://pararius.com{}", uri);
+
+                let raw_price = house.select_one_text(&self.price_selector)?;
+                let price: usize = if raw_price == "Price on request" {
+                    PRICE_ON_REQUEST
+                } else {
+                    raw_price
                         .split(" ")
                         .next()
-                        .unwrap_or(area)
+                        .unwrap()
+                        .replace("€", "")
+                        .replace(",", "")
                         .parse()
-                        .with_context(|| format!("invalid area: {area}"))?;
-
-                    anyhow::Ok(ScrapeResult::Partial(PartialScrapeResult {
-                        title: address.to_string(),
-                        price,
-                        url,
-                        area,
-                    }))
-                })
-                .try_collect()?)
+                        .with_context(|| format!("invalid price: {raw_price}"))?
+                };
+
+                let area = house.select_one_text(&self.area_selector)?;
+                let area = area
+                    .split(" ")
+                    .next()
+                    .unwrap_or(area)
+                    .parse()
+                    .with_context(|| format!("invalid area: {area}"))?;
+
+                let rooms = house
+                    .select_one_text(&self.rooms_selector)
+                    .ok()
+                    .and_then(leading_number);
+                let interior = house
+                    .select_one_text(&self.interior_selector)
+                    .ok()
+                    .and_then(|interior| interior.parse().ok());
+                let image_url = image_url(&house, &self.image_selector, &url);
+
+                anyhow::Ok(ScrapeResult::Partial(PartialScrapeResult {
+                    title: address.to_string(),
+                    price,
+                    url,
+                    area,
+                    rooms,
+                    interior,
+                    postcode,
+                    image_url,
+                    ..Default::default()
+                }))
+            });
+            listings.next_page = next_page_url(&document, &self.next_page_selector, &url);
+            Ok(listings)
         })
     }
 
     fn scrape_property(
         &self,
-        partial: PartialScrapeResult,
-    ) -> BoxFuture<anyhow::Result<FullScrapeResult>> {
+        mut partial: PartialScrapeResult,
+    ) -> BoxFuture<'_, anyhow::Result<FullScrapeResult>> {
         Box::pin(async {
-            let response = reqwest::get(&partial.url)
+            let response = self
+                .client
+                .get_text(&partial.url)
                 .await
-                .and_then(reqwest::Response::error_for_status)
-                .with_context(|| format!("failed to GET property at {}", partial.url))?
-                .text()
-                .await?;
+                .with_context(|| format!("failed to GET property at {}", partial.url))?;
 
             let appartment_document = Html::parse_document(&response);
             let map = appartment_document.select_one(&self.map_selector)?;
@@ -124,6 +173,23 @@ impl WebsiteScraper for ParariusScraper {
                 .parse()
                 .context("invalid latitude")?;
 
+            partial.bedrooms = appartment_document
+                .select_one_text(&self.bedrooms_selector)
+                .ok()
+                .and_then(leading_number);
+            partial.deposit = appartment_document
+                .select_one_text(&self.deposit_selector)
+                .ok()
+                .and_then(parse_euros);
+            partial.energy_label = appartment_document
+                .select_one_text(&self.energy_label_selector)
+                .ok()
+                .map(|label| label.to_uppercase());
+            partial.available_from = appartment_document
+                .select_one_text(&self.available_selector)
+                .ok()
+                .and_then(Availability::parse_text);
+
             Ok(FullScrapeResult {
                 partial,
                 location: geo::Point::new(longitude, latitude),
@@ -131,3 +197,166 @@ impl WebsiteScraper for ParariusScraper {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::scraping::{
+        testing::{rotterdam, stand_in},
+        Interior,
+    };
+
+    const LISTINGS: &str = include_str!("../../tests/fixtures/pararius/listings.html");
+    const LISTINGS_PAGE_2: &str =
+        include_str!("../../tests/fixtures/pararius/listings-page-2.html");
+    const PROPERTY: &str = include_str!("../../tests/fixtures/pararius/property.html");
+
+    #[tokio::test]
+    async fn lists_properties() {
+        let client = stand_in(&[("/apartments/rotterdam", LISTINGS)]).await;
+        let listings = ParariusScraper::new(client, rotterdam())
+            .list_properties()
+            .await
+            .unwrap();
+
+        assert_eq!(
+            listings.properties,
+            vec![
+                ScrapeResult::Partial(PartialScrapeResult {
+                    title: "Kralingse Plaslaan 12".to_string(),
+                    price: 1650,
+                    url: "https://pararius.com/apartment-for-rent/rotterdam/1a2b3c4d/kralingse-plaslaan"
+                        .to_string(),
+                    area: 72,
+                    rooms: Some(3),
+                    interior: Some(Interior::Upholstered),
+                    postcode: Some("3062 CA".to_string()),
+                    image_url: Some(
+                        "https://casco-media-prod.global.ssl.fastly.net/1a2b3c4d/kralingse-plaslaan/400x300.jpg"
+                            .to_string()
+                    ),
+                    ..Default::default()
+                }),
+                ScrapeResult::Partial(PartialScrapeResult {
+                    title: "Witte de Withstraat 40-B".to_string(),
+                    price: PRICE_ON_REQUEST,
+                    url: "https://pararius.com/apartment-for-rent/rotterdam/5e6f7a8b/witte-de-withstraat"
+                        .to_string(),
+                    area: 55,
+                    postcode: Some("3012 BR".to_string()),
+                    ..Default::default()
+                }),
+            ]
+        );
+        // The listing with a price we can't make sense of shouldn't lose the others
+        assert_eq!(listings.errors.len(), 1);
+        assert!(listings.errors[0].html.contains("Goudsesingel"));
+        assert_eq!(
+            listings.next_page.as_deref(),
+            Some("https://www.pararius.com/apartments/rotterdam/page-2")
+        );
+    }
+
+    #[tokio::test]
+    async fn searches_city_and_price_range() {
+        let client = stand_in(&[("/apartments/utrecht/0-1500", LISTINGS)]).await;
+        let search = Search {
+            city: "utrecht".to_string(),
+            min_price: None,
+            max_price: Some(1500),
+        };
+
+        let listings = ParariusScraper::new(client, search)
+            .list_properties()
+            .await
+            .unwrap();
+        assert_eq!(listings.properties.len(), 2);
+    }
+
+    fn urls(listings: &Listings) -> Vec<&str> {
+        listings
+            .properties
+            .iter()
+            .map(|property| property.url.as_str())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn follows_next_pages() {
+        let client = stand_in(&[
+            ("/apartments/rotterdam", LISTINGS),
+            ("/apartments/rotterdam/page-2", LISTINGS_PAGE_2),
+        ])
+        .await;
+        let scraper = ParariusScraper::new(client, rotterdam());
+
+        let listings = scraper.list_new_properties(&|_| false, 5).await.unwrap();
+        // The property which moved to the second page in the meantime is only listed once
+        assert_eq!(
+            urls(&listings),
+            vec![
+                "https://pararius.com/apartment-for-rent/rotterdam/1a2b3c4d/kralingse-plaslaan",
+                "https://pararius.com/apartment-for-rent/rotterdam/5e6f7a8b/witte-de-withstraat",
+                "https://pararius.com/apartment-for-rent/rotterdam/0b1c2d3e/mauritsweg",
+            ]
+        );
+        assert_eq!(listings.next_page, None);
+    }
+
+    #[tokio::test]
+    async fn stops_at_known_properties_or_page_limit() {
+        let client = stand_in(&[
+            ("/apartments/rotterdam", LISTINGS),
+            ("/apartments/rotterdam/page-2", LISTINGS_PAGE_2),
+        ])
+        .await;
+        let scraper = ParariusScraper::new(client, rotterdam());
+
+        let is_known = |url: &str| url.contains("kralingse-plaslaan");
+        let listings = scraper.list_new_properties(&is_known, 5).await.unwrap();
+        assert_eq!(listings.properties.len(), 2);
+
+        let listings = scraper.list_new_properties(&|_| false, 1).await.unwrap();
+        assert_eq!(listings.properties.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn scrapes_property() {
+        let client = stand_in(&[(
+            "/apartment-for-rent/rotterdam/1a2b3c4d/kralingse-plaslaan",
+            PROPERTY,
+        )])
+        .await;
+        let partial = PartialScrapeResult {
+            title: "Kralingse Plaslaan 12".to_string(),
+            price: 1650,
+            url: "https://pararius.com/apartment-for-rent/rotterdam/1a2b3c4d/kralingse-plaslaan"
+                .to_string(),
+            area: 72,
+            ..Default::default()
+        };
+
+        let full = ParariusScraper::new(client, rotterdam())
+            .scrape_property(partial.clone())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            full,
+            FullScrapeResult {
+                partial: PartialScrapeResult {
+                    bedrooms: Some(2),
+                    deposit: Some(3300),
+                    energy_label: Some("A".to_string()),
+                    available_from: Some(Availability::From(
+                        NaiveDate::from_ymd_opt(2025, 3, 1).unwrap()
+                    )),
+                    ..partial
+                },
+                location: geo::Point::new(4.5125, 51.9283),
+            }
+        );
+    }
+}
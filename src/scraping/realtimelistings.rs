@@ -0,0 +1,378 @@
+//! The realtime listings feed of broker websites built on the same CMS, such as Verra's.
+//! The feed has every property of the broker at once, both for rent and for sale.
+
+use std::collections::BTreeMap;
+
+use anyhow::Context;
+use futures::future::BoxFuture;
+use reqwest::Url;
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::{
+    http::HttpClient,
+    utils::{parse_euros, parse_postcode},
+    Availability, FullScrapeResult, ListingError, Listings, PartialScrapeResult, ScrapeResult,
+    WebsiteScraper,
+};
+
+/// A broker with a realtime listings feed, from the configuration.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RealtimeListingsDefinition {
+    /// The name of the website in the configuration and notifications, e.g. "verra".
+    name: String,
+    /// The website of the broker, e.g. "https://www.verra.nl".
+    base_url: String,
+    /// The city the broker is in, as used in URLs. The feed doesn't search,
+    /// so it's only scraped for this city.
+    city: String,
+    /// Where the feed is on the website.
+    #[serde(default = "default_feed_path")]
+    feed_path: String,
+    /// Which fields of the feed have the details, if they're named differently than Verra's.
+    #[serde(default)]
+    fields: FieldMapping,
+    /// Only properties with these values are listed, a list matches any of its values.
+    /// By default only available properties for rent are listed.
+    #[serde(default = "default_filters")]
+    filters: BTreeMap<String, Value>,
+}
+
+/// The names of the fields in the feed. Fields of nested objects are written like
+/// "location.lat".
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FieldMapping {
+    title: String,
+    price: String,
+    url: String,
+    area: String,
+    latitude: String,
+    longitude: String,
+    rooms: String,
+    bedrooms: String,
+    interior: String,
+    energy_label: String,
+    available_from: String,
+    deposit: String,
+    postcode: String,
+    image: String,
+}
+
+impl Default for FieldMapping {
+    fn default() -> Self {
+        Self {
+            title: "address".to_string(),
+            price: "rentalsPrice".to_string(),
+            url: "url".to_string(),
+            area: "livingSurface".to_string(),
+            latitude: "lat".to_string(),
+            longitude: "lng".to_string(),
+            rooms: "rooms".to_string(),
+            bedrooms: "bedrooms".to_string(),
+            interior: "furnishing".to_string(),
+            energy_label: "energyClass".to_string(),
+            available_from: "acceptance".to_string(),
+            deposit: "deposit".to_string(),
+            postcode: "zipcode".to_string(),
+            image: "photo".to_string(),
+        }
+    }
+}
+
+fn default_feed_path() -> String {
+    "/nl/realtime-listings/consumer".to_string()
+}
+
+fn default_filters() -> BTreeMap<String, Value> {
+    BTreeMap::from([
+        ("isRentals".to_string(), Value::Bool(true)),
+        ("status".to_string(), Value::from("Beschikbaar")),
+    ])
+}
+
+impl RealtimeListingsDefinition {
+    /// Verra Makelaars in Rotterdam, the first broker we found with this feed.
+    pub(super) fn verra() -> Self {
+        Self {
+            name: "verra".to_string(),
+            base_url: "https://www.verra.nl".to_string(),
+            city: "rotterdam".to_string(),
+            feed_path: default_feed_path(),
+            fields: FieldMapping::default(),
+            filters: default_filters(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub(crate) fn city(&self) -> &str {
+        &self.city
+    }
+
+    /// Check the base URL, so a typo shows up when loading the configuration.
+    pub(crate) fn validate(&self) -> anyhow::Result<()> {
+        Url::parse(&self.base_url)
+            .with_context(|| format!("invalid base_url of broker {}", self.name))?;
+        Ok(())
+    }
+
+    fn matches(&self, listing: &Value) -> bool {
+        self.filters.iter().all(|(field, expected)| {
+            let value = lookup(listing, field).unwrap_or(&Value::Null);
+            match expected {
+                Value::Array(options) => options.contains(value),
+                expected => value == expected,
+            }
+        })
+    }
+}
+
+/// The value of `field` in `listing`, `None` if it's missing or null.
+fn lookup<'a>(listing: &'a Value, field: &str) -> Option<&'a Value> {
+    field
+        .split('.')
+        .try_fold(listing, |value, key| value.get(key))
+        .filter(|value| !value.is_null())
+}
+
+fn text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(text) if !text.trim().is_empty() => Some(text.trim().to_string()),
+        Value::Number(number) => Some(number.to_string()),
+        _ => None,
+    }
+}
+
+/// Brokers fill in numbers as numbers, or as texts like "€ 1.500,-". Cents are ignored.
+fn number(value: &Value) -> Option<usize> {
+    match value {
+        Value::Number(number) => number
+            .as_u64()
+            .or_else(|| number.as_f64().filter(|n| *n >= 0.0).map(|n| n as u64))?
+            .try_into()
+            .ok(),
+        Value::String(text) => parse_euros(text),
+        _ => None,
+    }
+}
+
+fn coordinate(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(number) => number.as_f64(),
+        Value::String(text) => text.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+pub struct RealtimeListingsScraper {
+    client: HttpClient,
+    definition: RealtimeListingsDefinition,
+}
+
+impl RealtimeListingsScraper {
+    pub fn new(client: HttpClient, definition: RealtimeListingsDefinition) -> Self {
+        Self { client, definition }
+    }
+
+    fn parse_listing(&self, base_url: &Url, listing: &Value) -> anyhow::Result<ScrapeResult> {
+        let fields = &self.definition.fields;
+        let field = |name: &str| lookup(listing, name);
+
+        let title = field(&fields.title).and_then(text).context("no title")?;
+        let price = field(&fields.price).and_then(number).context("no price")?;
+        let area = field(&fields.area)
+            .and_then(number)
+            .and_then(|area| area.try_into().ok())
+            .context("no area")?;
+        let href = field(&fields.url).and_then(text).context("no URL")?;
+        let mut url = base_url.join(&href)?.to_string();
+        // Links without it are redirected
+        if !url.ends_with('/') {
+            url.push('/');
+        }
+        let latitude = field(&fields.latitude)
+            .and_then(coordinate)
+            .context("no latitude")?;
+        let longitude = field(&fields.longitude)
+            .and_then(coordinate)
+            .context("no longitude")?;
+
+        // Not every broker fills these in
+        let count = |name: &str| field(name).and_then(number)?.try_into().ok();
+        Ok(ScrapeResult::Full(FullScrapeResult {
+            partial: PartialScrapeResult {
+                title,
+                price,
+                url,
+                area,
+                rooms: count(&fields.rooms),
+                bedrooms: count(&fields.bedrooms),
+                interior: field(&fields.interior)
+                    .and_then(text)
+                    .and_then(|interior| interior.parse().ok()),
+                energy_label: field(&fields.energy_label)
+                    .and_then(text)
+                    .map(|label| label.to_uppercase()),
+                available_from: field(&fields.available_from)
+                    .and_then(text)
+                    .as_deref()
+                    .and_then(Availability::parse_text),
+                deposit: field(&fields.deposit).and_then(number),
+                postcode: field(&fields.postcode)
+                    .and_then(text)
+                    .as_deref()
+                    .and_then(parse_postcode),
+                image_url: field(&fields.image)
+                    .and_then(text)
+                    .and_then(|image| Some(base_url.join(&image).ok()?.to_string())),
+            },
+            location: geo::Point::new(longitude, latitude),
+        }))
+    }
+}
+
+impl WebsiteScraper for RealtimeListingsScraper {
+    fn list_properties(&self) -> BoxFuture<'_, anyhow::Result<Listings>> {
+        Box::pin(async {
+            let base_url = Url::parse(&self.definition.base_url)
+                .with_context(|| format!("invalid base URL {}", self.definition.base_url))?;
+            let feed_url = base_url.join(&self.definition.feed_path)?;
+            let houses: Vec<Value> = self.client.get_json(feed_url.as_str()).await?;
+
+            let mut listings = Listings::default();
+            for house in houses.iter().filter(|house| self.definition.matches(house)) {
+                match self.parse_listing(&base_url, house) {
+                    Ok(property) => listings.properties.push(property),
+                    Err(error) => listings.errors.push(ListingError {
+                        html: house.to_string(),
+                        error,
+                    }),
+                }
+            }
+            Ok(listings)
+        })
+    }
+
+    fn scrape_property(
+        &self,
+        _partial: PartialScrapeResult,
+    ) -> BoxFuture<'_, anyhow::Result<FullScrapeResult>> {
+        Box::pin(futures::future::ready(Err(anyhow::anyhow!(
+            "the realtime listings feed has every detail, properties aren't scraped one by one"
+        ))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scraping::{testing::stand_in, Interior};
+
+    const VERRA: &str = include_str!("../../tests/fixtures/verra/listings.json");
+    const BROKER: &str = include_str!("../../tests/fixtures/realtimelistings/listings.json");
+
+    #[tokio::test]
+    async fn lists_available_rentals() {
+        let client = stand_in(&[("/nl/realtime-listings/consumer", VERRA)]).await;
+        let properties = RealtimeListingsScraper::new(client, RealtimeListingsDefinition::verra())
+            .list_properties()
+            .await
+            .unwrap()
+            .properties;
+
+        assert_eq!(
+            properties,
+            vec![
+                ScrapeResult::Full(FullScrapeResult {
+                    partial: PartialScrapeResult {
+                        title: "Statenweg 120".to_string(),
+                        price: 1495,
+                        url: "https://www.verra.nl/nl/aanbod/rotterdam/statenweg-120/".to_string(),
+                        area: 75,
+                        rooms: Some(3),
+                        bedrooms: Some(2),
+                        interior: Some(Interior::Upholstered),
+                        energy_label: Some("C".to_string()),
+                        available_from: Some(Availability::Immediately),
+                        deposit: Some(2990),
+                        postcode: Some("3039 JK".to_string()),
+                        image_url: Some(
+                            "https://media.realworks.nl/verra/statenweg-120/1.jpg".to_string()
+                        ),
+                    },
+                    location: geo::Point::new(4.4662, 51.9316),
+                }),
+                ScrapeResult::Full(FullScrapeResult {
+                    partial: PartialScrapeResult {
+                        title: "Bergweg 55".to_string(),
+                        price: 1250,
+                        url: "https://www.verra.nl/nl/aanbod/rotterdam/bergweg-55/".to_string(),
+                        area: 58,
+                        ..Default::default()
+                    },
+                    location: geo::Point::new(4.4741, 51.9320),
+                }),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn maps_fields_and_filters_of_other_brokers() {
+        let definition: RealtimeListingsDefinition = toml::from_str(
+            r#"
+            name = "havenstad"
+            base_url = "https://www.havenstadwonen.nl"
+            city = "rotterdam"
+            feed_path = "/api/realtime-listings/consumer"
+            fields = { price = "rent.price", latitude = "location.lat", longitude = "location.lng" }
+            filters = { status = ["Beschikbaar", "Onder optie"] }
+            "#,
+        )
+        .unwrap();
+        let client = stand_in(&[("/api/realtime-listings/consumer", BROKER)]).await;
+        let listings = RealtimeListingsScraper::new(client, definition)
+            .list_properties()
+            .await
+            .unwrap();
+
+        assert_eq!(
+            listings.properties,
+            vec![
+                ScrapeResult::Full(FullScrapeResult {
+                    partial: PartialScrapeResult {
+                        title: "Heemraadssingel 140".to_string(),
+                        price: 1650,
+                        url: "https://www.havenstadwonen.nl/aanbod/heemraadssingel-140/"
+                            .to_string(),
+                        area: 82,
+                        rooms: Some(3),
+                        postcode: Some("3021 DL".to_string()),
+                        image_url: Some(
+                            "https://www.havenstadwonen.nl/media/heemraadssingel-140.jpg"
+                                .to_string()
+                        ),
+                        ..Default::default()
+                    },
+                    location: geo::Point::new(4.4545, 51.9160),
+                }),
+                ScrapeResult::Full(FullScrapeResult {
+                    partial: PartialScrapeResult {
+                        title: "Mathenesserdijk 33".to_string(),
+                        price: 1195,
+                        url: "https://www.havenstadwonen.nl/aanbod/mathenesserdijk-33/".to_string(),
+                        area: 54,
+                        ..Default::default()
+                    },
+                    location: geo::Point::new(4.4402, 51.9151),
+                }),
+            ]
+        );
+        // Without coordinates it can't be matched against regions
+        assert_eq!(listings.errors.len(), 1);
+        assert!(listings.errors[0].html.contains("Spanjaardstraat"));
+    }
+}
@@ -1,21 +1,30 @@
 use anyhow::Context;
 use futures::future::BoxFuture;
-use itertools::Itertools;
 use scraper::{Html, Selector};
 
 use super::{
-    utils::SelectExt, FullScrapeResult, PartialScrapeResult, ScrapeResult, WebsiteScraper,
+    http::HttpClient,
+    utils::{
+        image_url, leading_number, next_page_url, parse_euros, parse_listings, parse_postcode,
+        SelectExt,
+    },
+    Availability, FullScrapeResult, Listings, PartialScrapeResult, ScrapeResult, WebsiteScraper,
 };
 
 pub struct RotterdamWonenScraper {
+    client: HttpClient,
     houses_selector: Selector,
+    next_page_selector: Selector,
     area_selector: Selector,
+    image_selector: Selector,
 }
 
-impl Default for RotterdamWonenScraper {
-    fn default() -> Self {
+impl RotterdamWonenScraper {
+    pub fn new(client: HttpClient) -> Self {
         Self {
+            client,
             houses_selector: Selector::parse("div.property-list-item").unwrap(),
+            next_page_selector: Selector::parse("a.next.page-numbers").unwrap(),
             // I'm not 100% sure this will be consistent across all listings, but
             // it seems to be the case for all listings currently listed.
             // Maybe if there are more "meta" items the order will change.
@@ -23,73 +32,157 @@ impl Default for RotterdamWonenScraper {
                 ".property-meta-item.first-item > .property-meta-number",
             )
             .unwrap(),
+            image_selector: Selector::parse(".property-thumbnail img").unwrap(),
         }
     }
 }
 
 impl WebsiteScraper for RotterdamWonenScraper {
-    fn list_properties(&self) -> BoxFuture<anyhow::Result<Vec<ScrapeResult>>> {
-        Box::pin(async {
-            let response = reqwest::get("https://www.rotterdamwonen.nl/aanbod/?sortby=date-desc")
-                .await?
-                .error_for_status()?
-                .text()
-                .await?;
+    fn list_properties(&self) -> BoxFuture<'_, anyhow::Result<Listings>> {
+        self.list_page("https://www.rotterdamwonen.nl/aanbod/?sortby=date-desc".to_string())
+    }
+
+    fn list_page(&self, url: String) -> BoxFuture<'_, anyhow::Result<Listings>> {
+        Box::pin(async move {
+            let response = self.client.get_text(&url).await?;
 
             let document = Html::parse_document(&response);
             let houses = document.select(&self.houses_selector);
 
-            Ok(houses
-                .into_iter()
-                .map(|house| {
-                    let title = house.attr("data-title").unwrap_or_default().to_string();
-                    let price_raw = house.attr("data-price").context("no price")?;
-                    let price = price_raw
-                        .split_whitespace()
-                        .last()
-                        .with_context(|| format!("invalid price: {price_raw}"))?
-                        .parse()
-                        .with_context(|| format!("invalid price: {price_raw}"))?;
-
-                    let area_raw = house
-                        .select_one_text(&self.area_selector)
-                        .context("no area")?;
-                    let area = area_raw
-                        .parse()
-                        .with_context(|| format!("invalid area: {area_raw}"))?;
-
-                    let url = house.attr("data-link").context("no URL")?.to_string();
-
-                    let latitude_raw = house.attr("data-lat").context("no latitude")?;
-                    let longitude_raw = house.attr("data-long").context("no longitude")?;
-
-                    let latitude = latitude_raw
-                        .parse()
-                        .with_context(|| format!("invalid latitude: {latitude_raw}"))?;
-                    let longitude = longitude_raw
-                        .parse()
-                        .with_context(|| format!("invalid longitude: {longitude_raw}"))?;
-
-                    anyhow::Ok(ScrapeResult::Full(FullScrapeResult {
-                        partial: PartialScrapeResult {
-                            title,
-                            price,
-                            url,
-                            area,
-                        },
-                        location: geo::Point::new(longitude, latitude),
-                    }))
-                })
-                .try_collect()?)
+            let mut listings = parse_listings(houses, |house| {
+                let title = house.attr("data-title").unwrap_or_default().to_string();
+                let price_raw = house.attr("data-price").context("no price")?;
+                let price = price_raw
+                    .split_whitespace()
+                    .last()
+                    .with_context(|| format!("invalid price: {price_raw}"))?
+                    .parse()
+                    .with_context(|| format!("invalid price: {price_raw}"))?;
+
+                let area_raw = house
+                    .select_one_text(&self.area_selector)
+                    .context("no area")?;
+                let area = area_raw
+                    .parse()
+                    .with_context(|| format!("invalid area: {area_raw}"))?;
+
+                let url = house.attr("data-link").context("no URL")?.to_string();
+
+                let latitude_raw = house.attr("data-lat").context("no latitude")?;
+                let longitude_raw = house.attr("data-long").context("no longitude")?;
+
+                let latitude = latitude_raw
+                    .parse()
+                    .with_context(|| format!("invalid latitude: {latitude_raw}"))?;
+                let longitude = longitude_raw
+                    .parse()
+                    .with_context(|| format!("invalid longitude: {longitude_raw}"))?;
+
+                // The rest of the data attributes aren't set on every listing
+                let rooms = house.attr("data-rooms").and_then(leading_number);
+                let bedrooms = house.attr("data-bedrooms").and_then(leading_number);
+                let interior = house
+                    .attr("data-interior")
+                    .and_then(|interior| interior.parse().ok());
+                let energy_label = house
+                    .attr("data-energy-label")
+                    .filter(|label| !label.is_empty())
+                    .map(str::to_uppercase);
+                let available_from = house
+                    .attr("data-available")
+                    .and_then(Availability::parse_text);
+                let deposit = house.attr("data-deposit").and_then(parse_euros);
+                let postcode = house.attr("data-zipcode").and_then(parse_postcode);
+                let image_url = image_url(&house, &self.image_selector, &url);
+
+                anyhow::Ok(ScrapeResult::Full(FullScrapeResult {
+                    partial: PartialScrapeResult {
+                        title,
+                        price,
+                        url,
+                        area,
+                        rooms,
+                        bedrooms,
+                        interior,
+                        energy_label,
+                        available_from,
+                        deposit,
+                        postcode,
+                        image_url,
+                    },
+                    location: geo::Point::new(longitude, latitude),
+                }))
+            });
+            listings.next_page = next_page_url(&document, &self.next_page_selector, &url);
+            Ok(listings)
         })
     }
 
     fn scrape_property(
         &self,
         _partial: PartialScrapeResult,
-    ) -> BoxFuture<anyhow::Result<FullScrapeResult>> {
+    ) -> BoxFuture<'_, anyhow::Result<FullScrapeResult>> {
         Box::pin(futures::future::ready(Err(anyhow::anyhow!(
             "RotterdamWonen does not support scraping individual properties"
         ))))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::scraping::{testing::stand_in, Interior};
+
+    const LISTINGS: &str = include_str!("../../tests/fixtures/rotterdamwonen/listings.html");
+
+    #[tokio::test]
+    async fn lists_properties() {
+        let client = stand_in(&[("/aanbod/?sortby=date-desc", LISTINGS)]).await;
+        let properties = RotterdamWonenScraper::new(client)
+            .list_properties()
+            .await
+            .unwrap()
+            .properties;
+
+        assert_eq!(
+            properties,
+            vec![
+                ScrapeResult::Full(FullScrapeResult {
+                    partial: PartialScrapeResult {
+                        title: "Oostzeedijk 145".to_string(),
+                        price: 1350,
+                        url: "https://www.rotterdamwonen.nl/aanbod/oostzeedijk-145/".to_string(),
+                        area: 68,
+                        rooms: Some(3),
+                        bedrooms: Some(2),
+                        interior: Some(Interior::Upholstered),
+                        energy_label: Some("B".to_string()),
+                        available_from: Some(Availability::From(
+                            NaiveDate::from_ymd_opt(2025, 2, 15).unwrap()
+                        )),
+                        deposit: Some(2700),
+                        postcode: Some("3063 BE".to_string()),
+                        image_url: Some(
+                            "https://www.rotterdamwonen.nl/wp-content/uploads/2025/01/oostzeedijk-145-768x512.jpg"
+                                .to_string()
+                        ),
+                    },
+                    location: geo::Point::new(4.4962, 51.9205),
+                }),
+                ScrapeResult::Full(FullScrapeResult {
+                    partial: PartialScrapeResult {
+                        title: "Vierambachtsstraat 7".to_string(),
+                        price: 995,
+                        url: "https://www.rotterdamwonen.nl/aanbod/vierambachtsstraat-7/"
+                            .to_string(),
+                        area: 40,
+                        ..Default::default()
+                    },
+                    location: geo::Point::new(4.4541, 51.9139),
+                }),
+            ]
+        );
+    }
+}
@@ -0,0 +1,442 @@
+//! A scraper for websites described by a definition file, rather than by code of their own.
+//! Most small broker websites list their properties as cards with the same few details,
+//! so a selector for each detail is all it takes to scrape them.
+
+use std::path::Path;
+
+use anyhow::Context;
+use futures::future::BoxFuture;
+use itertools::Itertools;
+use reqwest::Url;
+use scraper::{ElementRef, Html, Selector};
+use serde::{Deserialize, Deserializer};
+
+use super::{
+    http::HttpClient,
+    utils::{next_page_url, parse_euros, parse_listings, parse_postcode, place_name, SelectExt},
+    Availability, FullScrapeResult, Listings, PartialScrapeResult, ScrapeResult, Search,
+    WebsiteScraper, PRICE_ON_REQUEST,
+};
+
+/// How to scrape a website, loaded from a TOML file. See `site.example.toml`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SelectorDefinition {
+    /// The name of the website in the configuration and notifications, e.g. "maasstad".
+    name: String,
+    /// The first page of listings, newest first. `{city}` is replaced by the city as used in
+    /// URLs, `{place}` by its name (e.g. "Den Haag"), and `{min_price}` and `{max_price}` by
+    /// the searched prices, or nothing.
+    list_url: String,
+    /// Every listing on the page.
+    #[serde(deserialize_with = "deserialize_selector")]
+    listing: Selector,
+    /// The link to the next page of listings, if the website has more than one.
+    #[serde(default, deserialize_with = "deserialize_optional_selector")]
+    next_page: Option<Selector>,
+    fields: ListingFields,
+    /// Where to find the coordinates on the property page, for listings without them.
+    property: Option<PropertyFields>,
+}
+
+/// The details of a listing, relative to the listing element.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ListingFields {
+    url: Field,
+    title: Field,
+    price: Field,
+    area: Field,
+    rooms: Option<Field>,
+    bedrooms: Option<Field>,
+    interior: Option<Field>,
+    energy_label: Option<Field>,
+    available_from: Option<Field>,
+    deposit: Option<Field>,
+    postcode: Option<Field>,
+    image: Option<Field>,
+    latitude: Option<Field>,
+    longitude: Option<Field>,
+}
+
+/// The details on the property page, relative to the whole page.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PropertyFields {
+    latitude: Field,
+    longitude: Field,
+}
+
+/// Where to find a detail, and how to clean it up.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Field {
+    /// The element with the detail, the listing (or page) itself if not given.
+    #[serde(default, deserialize_with = "deserialize_optional_selector")]
+    selector: Option<Selector>,
+    /// Read this attribute instead of the text, e.g. "href".
+    attribute: Option<String>,
+    /// Removed from the value, e.g. "€" or "p/m".
+    #[serde(default)]
+    strip: Vec<String>,
+    /// Removed from numbers, e.g. ".". Without it both "1.500,-" and "1,500.00" are understood,
+    /// but a website listing "1.500" as fifteen hundred euros with cents wouldn't be.
+    thousands_separator: Option<char>,
+    /// Values meaning the detail isn't known, e.g. "Prijs op aanvraag". Compared ignoring case.
+    #[serde(default)]
+    unknown: Vec<String>,
+}
+
+impl Field {
+    /// The cleaned up value in `element`, `None` if it's one of the unknown values.
+    /// Fails if there is no such element or attribute.
+    fn value(&self, element: ElementRef) -> anyhow::Result<Option<String>> {
+        let element = match &self.selector {
+            Some(selector) => element.select_one(selector)?,
+            None => element,
+        };
+        let raw = match &self.attribute {
+            Some(attribute) => element
+                .attr(attribute)
+                .with_context(|| format!("no attribute {attribute}"))?
+                .to_string(),
+            None => element.text().collect(),
+        };
+
+        let mut value = raw.split_whitespace().join(" ");
+        for strip in self.strip.iter() {
+            value = value.replace(strip.as_str(), "");
+        }
+        let value = value.trim();
+        let is_unknown = value.is_empty()
+            || self
+                .unknown
+                .iter()
+                .any(|unknown| value.eq_ignore_ascii_case(unknown));
+        Ok((!is_unknown).then(|| value.to_string()))
+    }
+
+    /// Parse the first number in `value`, ignoring any decimals.
+    fn number(&self, value: &str) -> Option<usize> {
+        let Some(separator) = self.thousands_separator else {
+            return parse_euros(value);
+        };
+        let value = value.replace(separator, "");
+        let start = value.find(|c: char| c.is_ascii_digit())?;
+        value[start..]
+            .chars()
+            .take_while(|c| c.is_ascii_digit())
+            .collect::<String>()
+            .parse()
+            .ok()
+    }
+
+    fn coordinate(&self, element: ElementRef) -> anyhow::Result<f64> {
+        let value = self.value(element)?.context("unknown coordinate")?;
+        value
+            .parse()
+            .with_context(|| format!("invalid coordinate: {value}"))
+    }
+}
+
+/// The value of an optional field, if the definition has it and it's known.
+fn optional_value(field: &Option<Field>, element: ElementRef) -> Option<String> {
+    field.as_ref()?.value(element).ok()?
+}
+
+fn optional_number(field: &Option<Field>, element: ElementRef) -> Option<usize> {
+    let field = field.as_ref()?;
+    field.number(&field.value(element).ok()??)
+}
+
+impl SelectorDefinition {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let raw = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read site definition {}", path.display()))?;
+        toml::from_str(&raw).with_context(|| format!("invalid site definition {}", path.display()))
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+pub struct SelectorScraper {
+    client: HttpClient,
+    search: Search,
+    definition: SelectorDefinition,
+}
+
+impl SelectorScraper {
+    pub fn new(client: HttpClient, search: Search, definition: SelectorDefinition) -> Self {
+        Self {
+            client,
+            search,
+            definition,
+        }
+    }
+
+    fn parse_listing(&self, listing: ElementRef, page_url: &str) -> anyhow::Result<ScrapeResult> {
+        let fields = &self.definition.fields;
+
+        let href = fields.url.value(listing)?.context("no link")?;
+        let url = Url::parse(page_url)?.join(&href)?.to_string();
+        let title = fields.title.value(listing)?.context("no title")?;
+
+        let price = match fields.price.value(listing)? {
+            Some(raw_price) => fields
+                .price
+                .number(&raw_price)
+                .with_context(|| format!("invalid price: {raw_price}"))?,
+            None => PRICE_ON_REQUEST,
+        };
+        let raw_area = fields.area.value(listing)?.context("no area")?;
+        let area = fields
+            .area
+            .number(&raw_area)
+            .and_then(|area| area.try_into().ok())
+            .with_context(|| format!("invalid area: {raw_area}"))?;
+
+        let partial = PartialScrapeResult {
+            title,
+            price,
+            url,
+            area,
+            rooms: optional_number(&fields.rooms, listing).and_then(|n| n.try_into().ok()),
+            bedrooms: optional_number(&fields.bedrooms, listing).and_then(|n| n.try_into().ok()),
+            interior: optional_value(&fields.interior, listing)
+                .and_then(|interior| interior.parse().ok()),
+            energy_label: optional_value(&fields.energy_label, listing)
+                .map(|label| label.to_uppercase()),
+            available_from: optional_value(&fields.available_from, listing)
+                .as_deref()
+                .and_then(Availability::parse_text),
+            deposit: optional_number(&fields.deposit, listing),
+            postcode: optional_value(&fields.postcode, listing)
+                .as_deref()
+                .and_then(parse_postcode),
+            image_url: optional_value(&fields.image, listing).and_then(|image| {
+                let url = Url::parse(page_url).ok()?.join(&image).ok()?;
+                Some(url.to_string())
+            }),
+        };
+
+        // Websites showing their listings on a map often have the coordinates in the listing
+        let location = fields
+            .latitude
+            .as_ref()
+            .zip(fields.longitude.as_ref())
+            .and_then(|(latitude, longitude)| {
+                Some(geo::Point::new(
+                    longitude.coordinate(listing).ok()?,
+                    latitude.coordinate(listing).ok()?,
+                ))
+            });
+        Ok(match location {
+            Some(location) => ScrapeResult::Full(FullScrapeResult { partial, location }),
+            None => ScrapeResult::Partial(partial),
+        })
+    }
+}
+
+impl WebsiteScraper for SelectorScraper {
+    fn list_properties(&self) -> BoxFuture<'_, anyhow::Result<Listings>> {
+        let price = |price: Option<usize>| price.map(|price| price.to_string()).unwrap_or_default();
+        let url = self
+            .definition
+            .list_url
+            .replace("{city}", &self.search.city)
+            .replace("{place}", &place_name(&self.search.city))
+            .replace("{min_price}", &price(self.search.min_price))
+            .replace("{max_price}", &price(self.search.max_price));
+        self.list_page(url)
+    }
+
+    fn list_page(&self, url: String) -> BoxFuture<'_, anyhow::Result<Listings>> {
+        Box::pin(async move {
+            let response = self.client.get_text(&url).await?;
+
+            let document = Html::parse_document(&response);
+            let listings = document.select(&self.definition.listing);
+
+            let mut listings =
+                parse_listings(listings, |listing| self.parse_listing(listing, &url));
+            listings.next_page = self
+                .definition
+                .next_page
+                .as_ref()
+                .and_then(|selector| next_page_url(&document, selector, &url));
+            Ok(listings)
+        })
+    }
+
+    fn scrape_property(
+        &self,
+        partial: PartialScrapeResult,
+    ) -> BoxFuture<'_, anyhow::Result<FullScrapeResult>> {
+        Box::pin(async move {
+            let property = self.definition.property.as_ref().with_context(|| {
+                format!(
+                    "the definition of {} has no property page",
+                    self.definition.name
+                )
+            })?;
+            let response = self
+                .client
+                .get_text(&partial.url)
+                .await
+                .with_context(|| format!("failed to GET property at {}", partial.url))?;
+
+            let document = Html::parse_document(&response);
+            let page = document.root_element();
+            let latitude = property.latitude.coordinate(page).context("no latitude")?;
+            let longitude = property
+                .longitude
+                .coordinate(page)
+                .context("no longitude")?;
+
+            Ok(FullScrapeResult {
+                partial,
+                location: geo::Point::new(longitude, latitude),
+            })
+        })
+    }
+}
+
+fn deserialize_selector<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Selector, D::Error> {
+    let raw = String::deserialize(deserializer)?;
+    Selector::parse(&raw)
+        .map_err(|e| serde::de::Error::custom(format!("invalid selector {raw:?}: {e}")))
+}
+
+fn deserialize_optional_selector<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Selector>, D::Error> {
+    deserialize_selector(deserializer).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::scraping::{
+        testing::{rotterdam, stand_in},
+        Interior,
+    };
+
+    const DEFINITION: &str = include_str!("../../tests/fixtures/selector/definition.toml");
+    const LISTINGS: &str = include_str!("../../tests/fixtures/selector/listings.html");
+    const PROPERTY: &str = include_str!("../../tests/fixtures/selector/property.html");
+
+    fn definition() -> SelectorDefinition {
+        toml::from_str(DEFINITION).unwrap()
+    }
+
+    #[test]
+    fn example_definition_is_valid() {
+        let example: SelectorDefinition =
+            toml::from_str(include_str!("../../site.example.toml")).unwrap();
+        assert_eq!(example.name(), "maasstad");
+    }
+
+    #[test]
+    fn rejects_invalid_selectors() {
+        let definition = DEFINITION.replace("\".object-card\"", "\"..object-card\"");
+        let error = toml::from_str::<SelectorDefinition>(&definition).unwrap_err();
+        assert!(error.to_string().contains("invalid selector"));
+    }
+
+    #[tokio::test]
+    async fn lists_properties() {
+        let client = stand_in(&[("/aanbod/huur/rotterdam?prijs_max=", LISTINGS)]).await;
+        let listings = SelectorScraper::new(client, rotterdam(), definition())
+            .list_properties()
+            .await
+            .unwrap();
+
+        assert_eq!(
+            listings.properties,
+            vec![
+                ScrapeResult::Full(FullScrapeResult {
+                    partial: PartialScrapeResult {
+                        title: "Noordsingel 87 A".to_string(),
+                        price: 1350,
+                        url: "https://www.maasstadmakelaars.nl/aanbod/huur/noordsingel-87-a"
+                            .to_string(),
+                        area: 62,
+                        rooms: Some(3),
+                        interior: Some(Interior::Upholstered),
+                        available_from: Some(Availability::From(
+                            NaiveDate::from_ymd_opt(2025, 5, 1).unwrap()
+                        )),
+                        postcode: Some("3035 EL".to_string()),
+                        image_url: Some(
+                            "https://www.maasstadmakelaars.nl/media/noordsingel-87-a/1.jpg"
+                                .to_string()
+                        ),
+                        ..Default::default()
+                    },
+                    location: geo::Point::new(4.4702, 51.9312),
+                }),
+                ScrapeResult::Partial(PartialScrapeResult {
+                    title: "Westersingel 12".to_string(),
+                    price: 2475,
+                    url: "https://www.maasstadmakelaars.nl/aanbod/huur/westersingel-12".to_string(),
+                    area: 104,
+                    rooms: Some(4),
+                    postcode: Some("3014 GN".to_string()),
+                    ..Default::default()
+                }),
+                ScrapeResult::Partial(PartialScrapeResult {
+                    title: "Coolsingel 1".to_string(),
+                    price: PRICE_ON_REQUEST,
+                    url: "https://www.maasstadmakelaars.nl/aanbod/huur/coolsingel-1".to_string(),
+                    area: 140,
+                    ..Default::default()
+                }),
+            ]
+        );
+        // The listing without an area
+        assert_eq!(listings.errors.len(), 1);
+        assert!(listings.errors[0].html.contains("Parkeerplaats"));
+        assert_eq!(
+            listings.next_page.as_deref(),
+            Some("https://www.maasstadmakelaars.nl/aanbod/huur/rotterdam?prijs_max=&pagina=2")
+        );
+    }
+
+    #[tokio::test]
+    async fn fills_in_search() {
+        let client = stand_in(&[("/aanbod/huur/den-haag?prijs_max=1500", LISTINGS)]).await;
+        let search = Search {
+            city: "den-haag".to_string(),
+            min_price: Some(1000),
+            max_price: Some(1500),
+        };
+
+        let listings = SelectorScraper::new(client, search, definition())
+            .list_properties()
+            .await
+            .unwrap();
+        assert_eq!(listings.properties.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn scrapes_coordinates_from_property_page() {
+        let client = stand_in(&[("/aanbod/huur/westersingel-12", PROPERTY)]).await;
+        let partial = PartialScrapeResult {
+            title: "Westersingel 12".to_string(),
+            price: 2475,
+            url: "https://www.maasstadmakelaars.nl/aanbod/huur/westersingel-12".to_string(),
+            area: 104,
+            ..Default::default()
+        };
+
+        let full = SelectorScraper::new(client, rotterdam(), definition())
+            .scrape_property(partial)
+            .await
+            .unwrap();
+        assert_eq!(full.location, geo::Point::new(4.4738, 51.9226));
+    }
+}
@@ -0,0 +1,130 @@
+//! Helpers for testing scrapers and the HTTP client against a local stand-in instead of the
+//! real websites.
+
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::Context;
+use reqwest::Url;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+    time::Instant,
+};
+
+use super::{http::HttpClient, Search};
+use crate::config::HttpConfig;
+
+/// Start a local stand-in for a website, serving `pages` (path and query to body) and 404 for
+/// anything else. Returns a client which sends all requests to the stand-in.
+pub(super) async fn stand_in(pages: &[(&'static str, &'static str)]) -> HttpClient {
+    let pages = pages.to_vec();
+    let (base, _) = serve(
+        move |path| match pages.iter().find(|(page, _)| *page == path) {
+            Some((_, body)) => response("200 OK", body),
+            None => response("404 Not Found", ""),
+        },
+    )
+    .await;
+
+    let config = HttpConfig {
+        max_retries: 0,
+        min_request_interval_ms: 0,
+        ..Default::default()
+    };
+    HttpClient::redirected(&config, base).unwrap()
+}
+
+/// Start a local stand-in which answers requests with `responses` (status line and headers,
+/// and body) in order, and 404 once they run out. Returns its URL and when each request came
+/// in, to test how the client retries and spaces out requests.
+pub(super) async fn scripted(
+    responses: &[(&'static str, &'static str)],
+) -> (Url, Arc<Mutex<Vec<Instant>>>) {
+    let responses = Mutex::new(VecDeque::from(responses.to_vec()));
+    serve(move |_| match responses.lock().unwrap().pop_front() {
+        Some((status, body)) => response(status, body),
+        None => response("404 Not Found", ""),
+    })
+    .await
+}
+
+fn response(status: &str, body: &str) -> String {
+    format!(
+        "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )
+}
+
+/// Serve every request with the response `respond` gives for its path and query.
+async fn serve(
+    respond: impl Fn(&str) -> String + Send + Sync + 'static,
+) -> (Url, Arc<Mutex<Vec<Instant>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let respond = Arc::new(respond);
+    let requests = Arc::new(Mutex::new(Vec::new()));
+
+    let received = requests.clone();
+    tokio::spawn(async move {
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let respond = respond.clone();
+            let received = received.clone();
+            tokio::spawn(async move {
+                let path = read_request_path(&mut stream).await.unwrap();
+                received.lock().unwrap().push(Instant::now());
+                let response = respond(&path);
+                stream.write_all(response.as_bytes()).await.unwrap();
+            });
+        }
+    });
+
+    let base = Url::parse(&format!("http://{address}")).unwrap();
+    (base, requests)
+}
+
+/// Read the request, returning the path and query of the request line.
+/// The body is read so the connection closes cleanly, but ignored otherwise.
+async fn read_request_path(stream: &mut tokio::net::TcpStream) -> anyhow::Result<String> {
+    let mut request = Vec::new();
+    let mut buffer = [0; 1024];
+    let headers_end = loop {
+        if let Some(end) = request.windows(4).position(|window| window == b"\r\n\r\n") {
+            break end + 4;
+        }
+        let read = stream.read(&mut buffer).await?;
+        anyhow::ensure!(read > 0, "connection closed");
+        request.extend_from_slice(&buffer[..read]);
+    };
+
+    let headers = String::from_utf8(request[..headers_end].to_vec())?;
+    let content_length: usize = headers
+        .lines()
+        .find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            name.eq_ignore_ascii_case("content-length")
+                .then(|| value.trim().parse().ok())?
+        })
+        .unwrap_or(0);
+    while request.len() < headers_end + content_length {
+        let read = stream.read(&mut buffer).await?;
+        anyhow::ensure!(read > 0, "connection closed");
+        request.extend_from_slice(&buffer[..read]);
+    }
+
+    let path = headers
+        .split_whitespace()
+        .nth(1)
+        .context("invalid request line")?;
+    Ok(path.to_string())
+}
+
+pub(super) fn rotterdam() -> Search {
+    Search {
+        city: "rotterdam".to_string(),
+        ..Default::default()
+    }
+}
@@ -1,15 +1,18 @@
 use anyhow::Context;
+use reqwest::Url;
 use scraper::{ElementRef, Html, Selector};
 
+use super::{ListingError, Listings, ScrapeResult};
+
 pub(super) trait SelectExt<'a> {
     fn select_one(&'a self, selector: &Selector) -> anyhow::Result<ElementRef<'a>>;
+    /// The first non-whitespace text in the element matching `selector`.
     fn select_one_text(&'a self, selector: &Selector) -> anyhow::Result<&'a str> {
-        Ok(self
-            .select_one(selector)?
+        self.select_one(selector)?
             .text()
-            .next()
-            .context("no text")?
-            .trim())
+            .map(str::trim)
+            .find(|text| !text.is_empty())
+            .context("no text")
     }
 }
 
@@ -28,3 +31,143 @@ impl<'a> SelectExt<'a> for ElementRef<'a> {
             .with_context(|| format!("no element matching {selector:?}"))
     }
 }
+
+/// Parse the number at the start of texts like "3 rooms" or "65 m²".
+pub(super) fn leading_number(text: &str) -> Option<u32> {
+    text.split_whitespace().next()?.parse().ok()
+}
+
+/// Parse an amount like "€ 1.500,-" or "€2,000 per month", ignoring any cents.
+pub(super) fn parse_euros(text: &str) -> Option<usize> {
+    let start = text.find(|c: char| c.is_ascii_digit())?;
+    let amount: String = text[start..]
+        .chars()
+        .take_while(|c| c.is_ascii_digit() || *c == '.' || *c == ',')
+        .collect();
+
+    // Websites use both "1.500,50" and "1,500.50", but either way a separator followed by
+    // three digits separates the thousands, and any other separator starts the cents.
+    let mut parts = amount.split(['.', ',']);
+    let mut euros = parts.next()?.to_string();
+    for part in parts.take_while(|part| part.len() == 3) {
+        euros.push_str(part);
+    }
+    euros.parse().ok()
+}
+
+/// Find a Dutch postcode in a text like "3011 AB Rotterdam (Centrum)",
+/// normalized to the "3011 AB" format.
+pub(super) fn parse_postcode(text: &str) -> Option<String> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    words.iter().enumerate().find_map(|(i, word)| {
+        let (digits, letters) = if word.len() == 6 && word.is_ascii() {
+            word.split_at(4)
+        } else {
+            (*word, *words.get(i + 1)?)
+        };
+
+        let is_postcode = digits.len() == 4
+            && digits.chars().all(|c| c.is_ascii_digit())
+            && letters.len() == 2
+            && letters.chars().all(|c| c.is_ascii_uppercase());
+        is_postcode.then(|| format!("{digits} {letters}"))
+    })
+}
+
+/// Parse every listing on a page on its own, so a single malformed listing
+/// doesn't lose all the others.
+pub(super) fn parse_listings<'a>(
+    listings: impl Iterator<Item = ElementRef<'a>>,
+    parse: impl Fn(ElementRef<'a>) -> anyhow::Result<ScrapeResult>,
+) -> Listings {
+    let mut result = Listings::default();
+    for listing in listings {
+        match parse(listing) {
+            Ok(property) => result.properties.push(property),
+            Err(error) => result.errors.push(ListingError {
+                html: listing.html(),
+                error,
+            }),
+        }
+    }
+    result
+}
+
+/// The absolute URL of the next page link matching `selector` on the page at `page_url`.
+pub(super) fn next_page_url(
+    document: &Html,
+    selector: &Selector,
+    page_url: &str,
+) -> Option<String> {
+    let href = document.select_one(selector).ok()?.attr("href")?;
+    let url = Url::parse(page_url).ok()?.join(href).ok()?;
+    Some(url.to_string())
+}
+
+/// The absolute URL of the image matching `selector` in a listing on the page at `page_url`.
+/// Lazy loaded images only have their real URL in `data-src`.
+pub(super) fn image_url(
+    listing: &ElementRef,
+    selector: &Selector,
+    page_url: &str,
+) -> Option<String> {
+    let image = listing.select_one(selector).ok()?;
+    let src = image.attr("data-src").or_else(|| image.attr("src"))?;
+    let url = Url::parse(page_url).ok()?.join(src).ok()?;
+    Some(url.to_string())
+}
+
+/// The name of a city as written by people rather than in URLs, e.g. "Den Haag" for "den-haag",
+/// for websites which search by place name.
+pub(super) fn place_name(city: &str) -> String {
+    city.split('-')
+        .map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect())
+                .unwrap_or_default()
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_places() {
+        assert_eq!(place_name("rotterdam"), "Rotterdam");
+        assert_eq!(place_name("den-haag"), "Den Haag");
+    }
+
+    #[test]
+    fn parses_euros() {
+        for (text, euros) in [
+            ("€ 1.500,-", Some(1500)),
+            ("€ 1.500,50 per maand", Some(1500)),
+            ("€2,000 per month", Some(2000)),
+            ("€2,000.50", Some(2000)),
+            ("€ 895 p/m", Some(895)),
+            ("€ 12.500.000 k.k.", Some(12_500_000)),
+            ("€ 950,00", Some(950)),
+            ("Prijs op aanvraag", None),
+        ] {
+            assert_eq!(parse_euros(text), euros, "{text}");
+        }
+    }
+
+    #[test]
+    fn parses_postcodes() {
+        for (text, postcode) in [
+            ("3011 AB Rotterdam (Centrum)", Some("3011 AB")),
+            ("3011AB Rotterdam", Some("3011 AB")),
+            ("Goudsesingel 12, 3011 KD", Some("3011 KD")),
+            ("Rotterdam 3011 ab", None),
+            ("Rotterdam (Centrum)", None),
+        ] {
+            assert_eq!(parse_postcode(text).as_deref(), postcode, "{text}");
+        }
+    }
+}
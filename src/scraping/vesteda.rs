@@ -0,0 +1,239 @@
+use anyhow::Context;
+use futures::future::BoxFuture;
+use scraper::{Html, Selector};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{
+    http::HttpClient,
+    utils::{parse_postcode, place_name, SelectExt},
+    FullScrapeResult, ListingError, Listings, PartialScrapeResult, ScrapeResult, Search,
+    WebsiteScraper,
+};
+
+/// Vesteda searches around a place, rather than within the city limits.
+const SEARCH_RADIUS_KM: u32 = 5;
+
+pub struct VestedaScraper {
+    client: HttpClient,
+    search: Search,
+    map_selector: Selector,
+}
+
+impl VestedaScraper {
+    pub fn new(client: HttpClient, search: Search) -> Self {
+        Self {
+            client,
+            search,
+            map_selector: Selector::parse(".o-map[data-latitude][data-longitude]").unwrap(),
+        }
+    }
+}
+
+impl WebsiteScraper for VestedaScraper {
+    fn list_properties(&self) -> BoxFuture<'_, anyhow::Result<Listings>> {
+        Box::pin(async {
+            let (price_from, price_to) = self.search.price_bounds();
+            let request = SearchRequest {
+                place: place_name(&self.search.city),
+                place_type: 1,
+                radius: SEARCH_RADIUS_KM,
+                price_from,
+                price_to,
+                // Newest first
+                sorting: 0,
+                language: "en",
+            };
+            let response: SearchResponse = self
+                .client
+                .post_json("https://www.vesteda.com/api/units/search/facet", &request)
+                .await?;
+
+            let mut listings = Listings::default();
+            for item in response.results.items.iter() {
+                match Unit::deserialize(item) {
+                    Ok(unit) => listings.properties.push(unit.into_property()),
+                    Err(error) => listings.errors.push(ListingError {
+                        html: item.to_string(),
+                        error: error.into(),
+                    }),
+                }
+            }
+            Ok(listings)
+        })
+    }
+
+    fn scrape_property(
+        &self,
+        partial: PartialScrapeResult,
+    ) -> BoxFuture<'_, anyhow::Result<FullScrapeResult>> {
+        Box::pin(async move {
+            let response = self
+                .client
+                .get_text(&partial.url)
+                .await
+                .with_context(|| format!("failed to GET property at {}", partial.url))?;
+
+            let document = Html::parse_document(&response);
+            let map = document.select_one(&self.map_selector)?;
+            let latitude: f64 = map
+                .attr("data-latitude")
+                .context("no latitude")?
+                .parse()
+                .context("invalid latitude")?;
+            let longitude: f64 = map
+                .attr("data-longitude")
+                .context("no longitude")?
+                .parse()
+                .context("invalid longitude")?;
+
+            Ok(FullScrapeResult {
+                partial,
+                location: geo::Point::new(longitude, latitude),
+            })
+        })
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SearchRequest {
+    place: String,
+    place_type: u32,
+    radius: u32,
+    price_from: usize,
+    /// Without a maximum every price is listed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    price_to: Option<usize>,
+    sorting: u32,
+    language: &'static str,
+}
+
+#[derive(Deserialize)]
+struct SearchResponse {
+    results: SearchResults,
+}
+
+#[derive(Deserialize)]
+struct SearchResults {
+    /// Parsed one by one, so a single odd unit doesn't break the others.
+    items: Vec<Value>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Unit {
+    street: String,
+    house_number: String,
+    house_number_addition: Option<String>,
+    #[serde(rename = "priceUnformatted")]
+    price: usize,
+    size: u32,
+    url: String,
+
+    postal_code: Option<String>,
+    #[serde(rename = "numberOfBedRooms")]
+    bedrooms: Option<u32>,
+    image_url: Option<String>,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+}
+
+impl Unit {
+    fn into_property(self) -> ScrapeResult {
+        let mut title = format!("{} {}", self.street, self.house_number);
+        if let Some(addition) = self.house_number_addition.filter(|a| !a.is_empty()) {
+            title.push_str(&format!("-{addition}"));
+        }
+
+        let partial = PartialScrapeResult {
+            title,
+            price: self.price,
+            url: format!("https://www.vesteda.com{}", self.url),
+            area: self.size,
+            bedrooms: self.bedrooms,
+            postcode: self.postal_code.as_deref().and_then(parse_postcode),
+            image_url: self.image_url,
+            ..Default::default()
+        };
+        // Not every unit has coordinates in the search results,
+        // the others have them on the unit page.
+        match self.latitude.zip(self.longitude) {
+            Some((latitude, longitude)) => ScrapeResult::Full(FullScrapeResult {
+                partial,
+                location: geo::Point::new(longitude, latitude),
+            }),
+            None => ScrapeResult::Partial(partial),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scraping::testing::{rotterdam, stand_in};
+
+    const SEARCH: &str = include_str!("../../tests/fixtures/vesteda/search.json");
+    const PROPERTY: &str = include_str!("../../tests/fixtures/vesteda/property.html");
+
+    #[tokio::test]
+    async fn lists_properties() {
+        let client = stand_in(&[("/api/units/search/facet", SEARCH)]).await;
+        let listings = VestedaScraper::new(client, rotterdam())
+            .list_properties()
+            .await
+            .unwrap();
+
+        // The unit without a price is reported, the others are still listed
+        assert_eq!(listings.errors.len(), 1);
+        assert!(listings.errors[0].html.contains("Coolsingel"));
+        assert_eq!(
+            listings.properties,
+            vec![
+                ScrapeResult::Full(FullScrapeResult {
+                    partial: PartialScrapeResult {
+                        title: "Wijnhaven 69-B".to_string(),
+                        price: 1565,
+                        url: "https://www.vesteda.com/en/unit-search/rotterdam/wijnhaven-69-b"
+                            .to_string(),
+                        area: 78,
+                        bedrooms: Some(2),
+                        postcode: Some("3011 WJ".to_string()),
+                        image_url: Some(
+                            "https://www.vesteda.com/media/units/31870/1.jpg".to_string()
+                        ),
+                        ..Default::default()
+                    },
+                    location: geo::Point::new(4.4893, 51.9181),
+                }),
+                ScrapeResult::Partial(PartialScrapeResult {
+                    title: "Boezemsingel 4".to_string(),
+                    price: 1295,
+                    url: "https://www.vesteda.com/en/unit-search/rotterdam/boezemsingel-4"
+                        .to_string(),
+                    area: 61,
+                    postcode: Some("3034 EE".to_string()),
+                    ..Default::default()
+                }),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn scrapes_coordinates_from_property_page() {
+        let client = stand_in(&[("/en/unit-search/rotterdam/boezemsingel-4", PROPERTY)]).await;
+        let partial = PartialScrapeResult {
+            title: "Boezemsingel 4".to_string(),
+            price: 1295,
+            url: "https://www.vesteda.com/en/unit-search/rotterdam/boezemsingel-4".to_string(),
+            area: 61,
+            ..Default::default()
+        };
+
+        let full = VestedaScraper::new(client, rotterdam())
+            .scrape_property(partial)
+            .await
+            .unwrap();
+        assert_eq!(full.location, geo::Point::new(4.4916, 51.9301));
+    }
+}
@@ -0,0 +1,196 @@
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::{
+    http::HttpClient, utils::parse_postcode, FullScrapeResult, ListingError, Listings,
+    PartialScrapeResult, ScrapeResult, Search, WebsiteScraper,
+};
+
+const AANBOD_URL: &str = "https://www.woonnetrijnmond.nl/api/aanbod";
+/// The scrapers of all cities in the region share the listing of one run.
+const AANBOD_MAX_AGE: Duration = Duration::from_secs(60);
+
+/// The municipalities in the Rotterdam region, where the housing corporations on
+/// Woonnet Rijnmond offer their social housing.
+pub(super) const REGION: [&str; 12] = [
+    "rotterdam",
+    "schiedam",
+    "vlaardingen",
+    "maassluis",
+    "capelle-aan-den-ijssel",
+    "krimpen-aan-den-ijssel",
+    "barendrecht",
+    "ridderkerk",
+    "albrandswaard",
+    "lansingerland",
+    "nissewaard",
+    "voorne-aan-zee",
+];
+
+pub struct WoonnetRijnmondScraper {
+    client: HttpClient,
+    search: Search,
+}
+
+impl WoonnetRijnmondScraper {
+    pub fn new(client: HttpClient, search: Search) -> Self {
+        Self { client, search }
+    }
+}
+
+impl WebsiteScraper for WoonnetRijnmondScraper {
+    fn list_properties(&self) -> BoxFuture<'_, anyhow::Result<Listings>> {
+        Box::pin(async {
+            // There's no search, the whole region is listed at once for every city in it
+            let aanbod = self
+                .client
+                .get_text_cached(AANBOD_URL, AANBOD_MAX_AGE)
+                .await?;
+            let aanbod: Aanbod = serde_json::from_str(&aanbod)?;
+
+            let mut listings = Listings::default();
+            for woning in aanbod.woningen.iter().filter(|woning| {
+                woning["plaats"].as_str().is_some_and(|plaats| {
+                    plaats.to_lowercase().replace(' ', "-") == self.search.city
+                })
+            }) {
+                match Woning::deserialize(woning) {
+                    Ok(parsed) => listings.properties.push(parsed.into_property()),
+                    Err(error) => listings.errors.push(ListingError {
+                        html: woning.to_string(),
+                        error: error.into(),
+                    }),
+                }
+            }
+            Ok(listings)
+        })
+    }
+
+    fn scrape_property(
+        &self,
+        _partial: PartialScrapeResult,
+    ) -> BoxFuture<'_, anyhow::Result<FullScrapeResult>> {
+        Box::pin(futures::future::ready(Err(anyhow::anyhow!(
+            "WoonnetRijnmond does not support scraping individual properties"
+        ))))
+    }
+}
+
+#[derive(Deserialize)]
+struct Aanbod {
+    /// Parsed one by one, so a single odd property doesn't break the others.
+    woningen: Vec<Value>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Woning {
+    straat: String,
+    huisnummer: String,
+    /// Including service costs.
+    totale_huur: f64,
+    woonoppervlakte: u32,
+    url: String,
+    latitude: f64,
+    longitude: f64,
+
+    huisletter: Option<String>,
+    postcode: Option<String>,
+    aantal_kamers: Option<u32>,
+    foto: Option<String>,
+}
+
+impl Woning {
+    fn into_property(self) -> ScrapeResult {
+        let mut title = format!("{} {}", self.straat, self.huisnummer);
+        if let Some(huisletter) = self.huisletter.filter(|h| !h.is_empty()) {
+            title.push_str(&format!(" {huisletter}"));
+        }
+
+        ScrapeResult::Full(FullScrapeResult {
+            partial: PartialScrapeResult {
+                title,
+                // Ignoring the cents, like the other websites
+                price: self.totale_huur as usize,
+                url: format!("https://www.woonnetrijnmond.nl{}", self.url),
+                area: self.woonoppervlakte,
+                rooms: self.aantal_kamers,
+                postcode: self.postcode.as_deref().and_then(parse_postcode),
+                image_url: self
+                    .foto
+                    .map(|foto| format!("https://www.woonnetrijnmond.nl{foto}")),
+                ..Default::default()
+            },
+            location: geo::Point::new(self.longitude, self.latitude),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scraping::testing::{rotterdam, stand_in};
+
+    const AANBOD: &str = include_str!("../../tests/fixtures/woonnetrijnmond/aanbod.json");
+
+    #[tokio::test]
+    async fn lists_properties_in_city() {
+        let client = stand_in(&[("/api/aanbod", AANBOD)]).await;
+        let listings = WoonnetRijnmondScraper::new(client.clone(), rotterdam())
+            .list_properties()
+            .await
+            .unwrap();
+
+        // The rent of one of them is missing
+        assert_eq!(listings.errors.len(), 1);
+        assert!(listings.errors[0].html.contains("Zwart Janstraat"));
+        // The property in Schiedam is left out
+        assert_eq!(
+            listings.properties,
+            vec![
+                ScrapeResult::Full(FullScrapeResult {
+                    partial: PartialScrapeResult {
+                        title: "Pleinweg 190 B".to_string(),
+                        price: 745,
+                        url: "https://www.woonnetrijnmond.nl/aanbod/348712".to_string(),
+                        area: 58,
+                        rooms: Some(3),
+                        postcode: Some("3083 EZ".to_string()),
+                        image_url: Some(
+                            "https://www.woonnetrijnmond.nl/media/aanbod/348712/hoofdfoto.jpg"
+                                .to_string()
+                        ),
+                        ..Default::default()
+                    },
+                    location: geo::Point::new(4.4792, 51.8946),
+                }),
+                ScrapeResult::Full(FullScrapeResult {
+                    partial: PartialScrapeResult {
+                        title: "Schiedamseweg 41".to_string(),
+                        price: 598,
+                        url: "https://www.woonnetrijnmond.nl/aanbod/348655".to_string(),
+                        area: 45,
+                        rooms: Some(2),
+                        postcode: Some("3026 AB".to_string()),
+                        ..Default::default()
+                    },
+                    location: geo::Point::new(4.4440, 51.9095),
+                }),
+            ]
+        );
+
+        let schiedam = Search {
+            city: "schiedam".to_string(),
+            ..Default::default()
+        };
+        let listings = WoonnetRijnmondScraper::new(client, schiedam)
+            .list_properties()
+            .await
+            .unwrap();
+        assert_eq!(listings.properties.len(), 1);
+        assert!(listings.errors.is_empty());
+    }
+}
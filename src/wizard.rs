@@ -0,0 +1,298 @@
+use geo::Point;
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup};
+
+use crate::{filter::Filter, location::Region};
+
+/// Callback data of the wizard's buttons starts with this, followed by the step and the answer.
+pub(super) const CALLBACK_PREFIX: &str = "wizard:";
+
+const BUDGETS: [usize; 6] = [1000, 1250, 1500, 1750, 2000, 2500];
+const AREAS: [u32; 6] = [30, 40, 50, 60, 75, 90];
+const ROOMS: [u32; 4] = [1, 2, 3, 4];
+const RADII_KM: [u32; 4] = [1, 2, 5, 10];
+
+/// The name of the region created by the wizard, answering again replaces it.
+const REGION_NAME: &str = "home";
+
+/// Walks a new subscriber through the most important settings with inline keyboards,
+/// one question at a time.
+#[derive(Debug, Clone)]
+pub(super) struct Wizard {
+    step: Step,
+    cities: Vec<String>,
+    /// `Some(None)` means all cities.
+    city: Option<Option<String>>,
+    max_price: Option<usize>,
+    min_area: Option<u32>,
+    min_rooms: Option<u32>,
+    location: Option<Point>,
+    region: Option<Region>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Step {
+    City,
+    Budget,
+    Area,
+    Rooms,
+    Region,
+    /// Waiting for the subscriber to send a location, rather than for a button.
+    Location,
+    Radius,
+    Done,
+}
+
+impl Step {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Step::City => "city",
+            Step::Budget => "budget",
+            Step::Area => "area",
+            Step::Rooms => "rooms",
+            Step::Region => "region",
+            Step::Location => "location",
+            Step::Radius => "radius",
+            Step::Done => "done",
+        }
+    }
+}
+
+impl Wizard {
+    /// Start the wizard. Choosing a city is skipped if there's only one.
+    pub(super) fn new(cities: Vec<String>) -> Self {
+        Self {
+            step: if cities.len() > 1 {
+                Step::City
+            } else {
+                Step::Budget
+            },
+            cities,
+            city: None,
+            max_price: None,
+            min_area: None,
+            min_rooms: None,
+            location: None,
+            region: None,
+        }
+    }
+
+    pub(super) fn is_done(&self) -> bool {
+        self.step == Step::Done
+    }
+
+    /// The question for the current step, with the buttons to answer it.
+    /// There are no buttons when waiting for a location.
+    pub(super) fn question(&self) -> (String, Option<InlineKeyboardMarkup>) {
+        let (text, buttons): (&str, Vec<(String, String)>) = match self.step {
+            Step::City => (
+                "Which city are you looking in?",
+                self.cities
+                    .iter()
+                    .map(|city| (city.clone(), city.clone()))
+                    .chain([("All cities".to_string(), "all".to_string())])
+                    .collect(),
+            ),
+            Step::Budget => (
+                "What's the most you want to pay per month?",
+                BUDGETS
+                    .iter()
+                    .map(|budget| (format!("€{budget}"), budget.to_string()))
+                    .chain([("No limit".to_string(), "any".to_string())])
+                    .collect(),
+            ),
+            Step::Area => (
+                "How big should it be at least?",
+                AREAS
+                    .iter()
+                    .map(|area| (format!("{area} m²"), area.to_string()))
+                    .chain([("Any size".to_string(), "any".to_string())])
+                    .collect(),
+            ),
+            Step::Rooms => (
+                "How many rooms do you need at least?",
+                ROOMS
+                    .iter()
+                    .map(|rooms| (rooms.to_string(), rooms.to_string()))
+                    .chain([("Any".to_string(), "any".to_string())])
+                    .collect(),
+            ),
+            Step::Region => (
+                "Do you only want properties near a certain location? \
+                This replaces the regions you added before.",
+                vec![
+                    ("Yes".to_string(), "yes".to_string()),
+                    ("No".to_string(), "no".to_string()),
+                ],
+            ),
+            Step::Location => (
+                "Send me the location, using the paperclip button",
+                Vec::new(),
+            ),
+            Step::Radius => (
+                "How far from it can the property be?",
+                RADII_KM
+                    .iter()
+                    .map(|radius| (format!("{radius} km"), radius.to_string()))
+                    .collect(),
+            ),
+            Step::Done => (
+                "All set! You'll get new properties matching your search. \
+                Use /status to see your settings, or /start to change them.",
+                Vec::new(),
+            ),
+        };
+
+        let buttons: Vec<_> = buttons
+            .into_iter()
+            .map(|(label, answer)| {
+                let data = format!("{CALLBACK_PREFIX}{}:{answer}", self.step.as_str());
+                InlineKeyboardButton::callback(label, data)
+            })
+            .collect();
+        let keyboard = (!buttons.is_empty())
+            .then(|| InlineKeyboardMarkup::new(buttons.chunks(3).map(|row| row.to_vec())));
+        (text.to_string(), keyboard)
+    }
+
+    /// Handle a button press, with the callback data after `CALLBACK_PREFIX`.
+    /// Returns false for invalid answers and buttons of earlier questions.
+    pub(super) fn answer(&mut self, data: &str) -> bool {
+        let Some((step, answer)) = data.split_once(':') else {
+            return false;
+        };
+        if step != self.step.as_str() {
+            return false;
+        }
+
+        let next = match self.step {
+            Step::City if answer == "all" => {
+                self.city = Some(None);
+                Step::Budget
+            }
+            Step::City if self.cities.iter().any(|city| city == answer) => {
+                self.city = Some(Some(answer.to_string()));
+                Step::Budget
+            }
+            Step::Budget => match parse_answer(answer) {
+                Some(max_price) => {
+                    self.max_price = max_price;
+                    Step::Area
+                }
+                None => return false,
+            },
+            Step::Area => match parse_answer(answer) {
+                Some(min_area) => {
+                    self.min_area = min_area;
+                    Step::Rooms
+                }
+                None => return false,
+            },
+            Step::Rooms => match parse_answer(answer) {
+                Some(min_rooms) => {
+                    self.min_rooms = min_rooms;
+                    Step::Region
+                }
+                None => return false,
+            },
+            Step::Region if answer == "yes" => Step::Location,
+            Step::Region if answer == "no" => Step::Done,
+            Step::Radius => {
+                let (Ok(radius), Some(location)) = (answer.parse::<u32>(), self.location) else {
+                    return false;
+                };
+                let radius = f64::from(radius) * 1000.0;
+                self.region = Some(Region::circle(REGION_NAME.to_string(), location, radius));
+                Step::Done
+            }
+            _ => return false,
+        };
+
+        self.step = next;
+        true
+    }
+
+    /// Handle the location the subscriber sent. Returns false if we weren't waiting for one.
+    pub(super) fn located(&mut self, location: Point) -> bool {
+        if self.step != Step::Location {
+            return false;
+        }
+        self.location = Some(location);
+        self.step = Step::Radius;
+        true
+    }
+
+    /// Update `filter` with the answers.
+    pub(super) fn apply(&self, filter: &mut Filter) {
+        filter.max_price = self.max_price;
+        filter.min_area = self.min_area;
+        filter.min_rooms = self.min_rooms;
+    }
+
+    /// The cities to follow, if the subscriber chose them. An empty list means all cities.
+    pub(super) fn cities(&self) -> Option<Vec<String>> {
+        self.city.clone().map(|city| city.into_iter().collect())
+    }
+
+    pub(super) fn region(&self) -> Option<&Region> {
+        self.region.as_ref()
+    }
+}
+
+/// "any" is `Some(None)`, a number is `Some(Some(number))`.
+fn parse_answer<T: std::str::FromStr>(answer: &str) -> Option<Option<T>> {
+    if answer == "any" {
+        return Some(None);
+    }
+    answer.parse().ok().map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn answer(wizard: &mut Wizard, data: &str) -> bool {
+        wizard.answer(data.strip_prefix(CALLBACK_PREFIX).unwrap())
+    }
+
+    #[test]
+    fn walks_through_all_steps() {
+        let mut wizard = Wizard::new(vec!["rotterdam".to_string(), "utrecht".to_string()]);
+
+        assert!(answer(&mut wizard, "wizard:city:utrecht"));
+        assert!(answer(&mut wizard, "wizard:budget:1500"));
+        assert!(answer(&mut wizard, "wizard:area:any"));
+        assert!(answer(&mut wizard, "wizard:rooms:2"));
+        assert!(answer(&mut wizard, "wizard:region:yes"));
+        // The location is sent as a message rather than with a button
+        assert!(!answer(&mut wizard, "wizard:location:here"));
+        assert!(wizard.located(Point::new(4.4777, 51.9244)));
+        assert!(answer(&mut wizard, "wizard:radius:2"));
+        assert!(wizard.is_done());
+
+        let mut filter = Filter {
+            min_area: Some(55),
+            ..Default::default()
+        };
+        wizard.apply(&mut filter);
+        assert_eq!(filter.max_price, Some(1500));
+        assert_eq!(filter.min_area, None);
+        assert_eq!(filter.min_rooms, Some(2));
+        assert_eq!(wizard.cities(), Some(vec!["utrecht".to_string()]));
+        assert!(wizard
+            .region()
+            .unwrap()
+            .contains(&Point::new(4.4777, 51.9244)));
+    }
+
+    #[test]
+    fn ignores_old_and_invalid_answers() {
+        let mut wizard = Wizard::new(vec!["rotterdam".to_string()]);
+
+        // With a single city there's nothing to choose
+        assert!(!answer(&mut wizard, "wizard:city:rotterdam"));
+        assert!(!answer(&mut wizard, "wizard:budget:lots"));
+        assert!(answer(&mut wizard, "wizard:budget:any"));
+        assert!(!answer(&mut wizard, "wizard:budget:1000"));
+        assert_eq!(wizard.cities(), None);
+    }
+}